@@ -0,0 +1,162 @@
+use std::{borrow::Cow, ops::Range};
+
+use ropey::Rope;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The text being edited, stored as a rope so that line lookups and edits
+/// are `O(log n)` regardless of the size of the document.
+///
+/// Positions inside the whole text are byte offsets, while columns inside a
+/// line are counted in extended grapheme clusters, which is what the user
+/// sees as a single character. Lines are separated by `'\n'` only, which means
+/// that a text ending with a line break has an empty last line, just like the
+/// cursor sees it.
+#[derive(Clone)]
+pub struct Buffer {
+    rope: Rope,
+}
+
+impl Buffer {
+    pub fn new(data: &str) -> Self {
+        Self {
+            rope: Rope::from_str(data),
+        }
+    }
+
+    /// Returns the total number of lines, including the empty line after a trailing `'\n'`.
+    #[inline]
+    pub fn len_lines(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    /// Returns the total number of bytes of the text.
+    #[inline]
+    pub fn len_bytes(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    /// Returns the line that contains the byte offset `pos`.
+    #[inline]
+    pub fn byte_to_line(&self, pos: usize) -> usize {
+        self.rope.byte_to_line(pos)
+    }
+
+    /// Returns the byte offset where the line `n` starts.
+    #[inline]
+    pub fn line_to_byte(&self, n: usize) -> usize {
+        self.rope.line_to_byte(n)
+    }
+
+    /// Returns the grapheme column and the line, as `(cx, cy)`, of the byte offset `pos`.
+    pub fn byte_to_cursor(&self, pos: usize) -> (usize, usize) {
+        let n = self.rope.byte_to_line(pos);
+        let line = self.line(n);
+        let offset = (pos - self.line_to_byte(n)).min(line.len());
+
+        (line[..offset].graphemes(true).count(), n)
+    }
+
+    /// Returns the width, in graphemes, of the line `n` without its line break.
+    pub fn line_width(&self, n: usize) -> usize {
+        self.line(n).graphemes(true).count()
+    }
+
+    /// Returns the byte offset of the grapheme at column `col` of the line `n`.
+    /// Columns past the end of the line are clamped to the end of the line.
+    pub fn grapheme_to_byte(&self, n: usize, col: usize) -> usize {
+        let line = self.line(n);
+        let offset = line
+            .grapheme_indices(true)
+            .nth(col)
+            .map_or(line.len(), |(i, _)| i);
+
+        self.line_to_byte(n) + offset
+    }
+
+    /// Returns the content of the line `n` without its line break.
+    ///
+    /// The text is only copied when the line is split across multiple chunks of the rope.
+    pub fn line(&self, n: usize) -> Cow<'_, str> {
+        let line = self.rope.line(n);
+        let mut len = line.len_bytes();
+
+        if len > 0 && line.byte(len - 1) == b'\n' {
+            len -= 1;
+        }
+
+        line.byte_slice(..len).into()
+    }
+
+    /// Returns the text between the byte offsets of `range`.
+    pub fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        self.rope.byte_slice(range).into()
+    }
+
+    /// Returns the text between the byte offsets of `range`, or `None` when the range goes past
+    /// the end of the text or through a character.
+    pub fn get_slice(&self, range: Range<usize>) -> Option<Cow<'_, str>> {
+        self.rope.get_byte_slice(range).map(Into::into)
+    }
+
+    /// Returns the character that starts at the byte offset `pos`.
+    pub fn char_at(&self, pos: usize) -> Option<char> {
+        self.rope.get_char(self.rope.try_byte_to_char(pos).ok()?)
+    }
+
+    /// Iterates over the characters from the byte offset `pos` onwards, along with their offsets.
+    pub fn chars_from(&self, pos: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        let mut offset = pos;
+
+        self.rope.chars_at(self.rope.byte_to_char(pos)).map(move |c| {
+            let pos = offset;
+            offset += c.len_utf8();
+
+            (pos, c)
+        })
+    }
+
+    /// Iterates backwards over the characters before the byte offset `pos`, along with their offsets.
+    pub fn chars_before(&self, pos: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        let mut offset = pos;
+        let mut chars = self.rope.chars_at(self.rope.byte_to_char(pos));
+
+        std::iter::from_fn(move || {
+            let c = chars.prev()?;
+            offset -= c.len_utf8();
+
+            Some((offset, c))
+        })
+    }
+
+    /// Returns a hash of the whole text.
+    pub fn hash(&self) -> u64 {
+        crate::util::fnv1a(self.rope.bytes())
+    }
+
+    /// Inserts `text` at the byte offset `pos`.
+    pub fn insert(&mut self, pos: usize, text: &str) {
+        let char_idx = self.rope.byte_to_char(pos);
+        self.rope.insert(char_idx, text);
+    }
+
+    /// Removes the text between the byte offsets of `range` and returns it.
+    pub fn remove(&mut self, range: Range<usize>) -> String {
+        let start = self.rope.byte_to_char(range.start);
+        let end = self.rope.byte_to_char(range.end);
+
+        let removed = self.rope.slice(start..end).to_string();
+        self.rope.remove(start..end);
+
+        removed
+    }
+}
+
+impl std::fmt::Display for Buffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for chunk in self.rope.chunks() {
+            f.write_str(chunk)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,555 @@
+use super::{key::Key, movement::{Find, Motion}, operator::Operator, textobject::{ObjectKind, TextObject}, viewport::{Align, Scroll}, visual_mode::VisualKind};
+
+/// What an operator acts on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Target {
+    /// The text between the cursor and where the motion moves it to.
+    Motion(Motion),
+    /// Whole lines, starting at the cursor's line, like in `dd` or `>>`.
+    Line,
+    /// The text a text object selects around the cursor, like in `ci"`.
+    Object(TextObject),
+    /// The visual selection, or the lines it goes through as a whole when `lines` is set, like
+    /// with `D` or `Y` in visual mode.
+    Selection { lines: bool },
+}
+
+/// Where insert mode starts when entering it from normal mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Insert {
+    /// `i`, before the cursor.
+    Before,
+    /// `a`, after the cursor.
+    After,
+    /// `I`, before the first non-blank character of the line.
+    LineStart,
+    /// `A`, at the end of the line.
+    LineEnd,
+    /// `o`, in a new line below the cursor.
+    LineBelow,
+    /// `O`, in a new line above the cursor.
+    LineAbove,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Move(Motion),
+    Operate(Operator, Target),
+    Insert(Insert),
+    /// `v`, `V` and `Ctrl-V`, which start or switch the kind of a visual selection.
+    Visual(VisualKind),
+    /// `gv`, selects the last visual selection again.
+    Reselect,
+    /// `o` and `O` in visual mode, which move the cursor to the other end of the selection.
+    SwapEnds { column: bool },
+    /// A text object typed in visual mode, which selects the text it covers.
+    Select(TextObject),
+    /// `p` and `P`, which put the text of a register after or before the cursor. `]p` and `[p`
+    /// also adjust the indentation of the lines put.
+    Put { before: bool, indent: bool },
+    /// `.`, repeats the last change.
+    Repeat,
+    /// `/` and `?`, start typing a search.
+    Search { forward: bool },
+    /// `:`, starts typing a command.
+    Ex,
+    /// `q{register}`, starts recording a macro.
+    Record(char),
+    /// `@{register}`, runs a macro, or the last one run with `@@`.
+    Play(char),
+    /// `m{name}`, marks the position of the cursor.
+    Mark(char),
+    /// `Ctrl-E`, `Ctrl-Y`, `Ctrl-D`, `Ctrl-U`, `Ctrl-F` and `Ctrl-B`, scroll the text.
+    Scroll(Scroll),
+    /// `zt`, `zz` and `zb`, scroll the text around the line of the cursor.
+    Align(Align),
+    Undo,
+    Redo,
+    /// `g-`, goes back in time through the undo tree.
+    EarlierState,
+    /// `g+`, goes forward in time through the undo tree.
+    LaterState,
+    Write,
+    /// `~`, switches the case of the characters under the cursor.
+    ToggleCase,
+    /// `J`, joins lines.
+    Join,
+    /// `r`, replaces the characters under the cursor.
+    Replace(char),
+}
+
+impl Action {
+    /// Whether the action changes the buffer, so `.` can repeat it.
+    pub fn is_change(&self) -> bool {
+        match self {
+            Action::Operate(operator, _) => *operator != Operator::Yank,
+            Action::Insert(_) | Action::ToggleCase | Action::Join | Action::Replace(_) | Action::Put { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// A normal mode command, following `[count]["register]action`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Command {
+    /// The count given to the command. Counts given both before the operator and before its
+    /// motion are multiplied, so `2d3w` has a count of 6.
+    pub count: Option<usize>,
+    pub register: Option<char>,
+    pub action: Action,
+}
+
+impl Command {
+    /// Returns the count given to the command, defaulting to `1`.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count.unwrap_or(1)
+    }
+}
+
+/// The result of parsing the keys typed in normal mode.
+pub enum Parse {
+    /// The keys make up a whole command.
+    Done(Command),
+    /// The keys are the beginning of a command, so more keys are needed.
+    Pending,
+    /// The keys can never make up a command.
+    Invalid,
+}
+
+/// Parses the keys typed in normal mode into a command.
+///
+/// ### Example
+/// ```
+/// let keys = [Key::Char('2'), Key::Char('d'), Key::Char('3'), Key::Char('w')];
+///
+/// match parse(&keys) {
+///     Parse::Done(command) => assert_eq!(command.count, Some(6)),
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn parse(keys: &[Key]) -> Parse {
+    parse_with(keys, Parser::command)
+}
+
+/// Parses the keys typed in visual mode into a command. Operators act on the selection right
+/// away, and `i` and `a` start text objects instead of insert mode.
+pub fn parse_visual(keys: &[Key]) -> Parse {
+    parse_with(keys, Parser::visual_command)
+}
+
+fn parse_with<'a>(keys: &'a [Key], command: fn(&mut Parser<'a>) -> Result<Command, Incomplete>) -> Parse {
+    let mut parser = Parser { keys, pos: 0 };
+
+    match command(&mut parser) {
+        Ok(command) if parser.pos == keys.len() => Parse::Done(command),
+        Ok(_) => Parse::Invalid,
+        Err(Incomplete::Pending) => Parse::Pending,
+        Err(Incomplete::Invalid) => Parse::Invalid,
+    }
+}
+
+enum Incomplete {
+    Pending,
+    Invalid,
+}
+
+struct Parser<'a> {
+    keys: &'a [Key],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Result<Key, Incomplete> {
+        let key = self.keys.get(self.pos).copied().ok_or(Incomplete::Pending)?;
+        self.pos += 1;
+
+        Ok(key)
+    }
+
+    fn next_char(&mut self) -> Result<char, Incomplete> {
+        match self.next()? {
+            Key::Char(c) => Ok(c),
+            _ => Err(Incomplete::Invalid),
+        }
+    }
+
+    fn peek(&self) -> Option<Key> {
+        self.keys.get(self.pos).copied()
+    }
+
+    /// Parses a count, which cannot start with `0` as that is a motion on its own.
+    fn count(&mut self) -> Option<usize> {
+        let mut count: Option<usize> = None;
+
+        while let Some(Key::Char(c)) = self.peek() {
+            match c.to_digit(10) {
+                Some(0) if count.is_none() => break,
+                Some(d) => {
+                    count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(d as usize));
+                    self.pos += 1;
+                },
+                None => break,
+            }
+        }
+
+        count
+    }
+
+    /// Parses the count and the register typed before a command, in either order.
+    fn prefix(&mut self) -> Result<(Option<usize>, Option<char>), Incomplete> {
+        let mut count = self.count();
+
+        let mut register = None;
+        if self.peek() == Some(Key::Char('"')) {
+            self.pos += 1;
+            register = Some(self.next_char()?);
+            count = mul(count, self.count());
+        }
+
+        Ok((count, register))
+    }
+
+    fn command(&mut self) -> Result<Command, Incomplete> {
+        let (mut count, register) = self.prefix()?;
+
+        let start = self.pos;
+        let action = match self.next()? {
+            Key::Char('i') => Action::Insert(Insert::Before),
+            Key::Char('a') => Action::Insert(Insert::After),
+            Key::Char('I') => Action::Insert(Insert::LineStart),
+            Key::Char('A') => Action::Insert(Insert::LineEnd),
+            Key::Char('o') => Action::Insert(Insert::LineBelow),
+            Key::Char('O') => Action::Insert(Insert::LineAbove),
+            Key::Char('v') => Action::Visual(VisualKind::Char),
+            Key::Char('V') => Action::Visual(VisualKind::Line),
+            Key::Ctrl('v') => Action::Visual(VisualKind::Block),
+            Key::Char('p') => Action::Put { before: false, indent: false },
+            Key::Char('P') => Action::Put { before: true, indent: false },
+            Key::Char(c @ (']' | '[')) => match self.next_char()? {
+                'p' => Action::Put { before: c == '[', indent: true },
+                'P' => Action::Put { before: true, indent: true },
+                _ => return Err(Incomplete::Invalid),
+            },
+            Key::Char('.') => Action::Repeat,
+            Key::Char('/') => Action::Search { forward: true },
+            Key::Char('?') => Action::Search { forward: false },
+            Key::Char(':') => Action::Ex,
+            Key::Char('q') => Action::Record(self.next_char()?),
+            Key::Char('@') => Action::Play(self.next_char()?),
+            Key::Char('m') => Action::Mark(self.next_char()?),
+            Key::Char('u') => Action::Undo,
+            Key::Ctrl('r') => Action::Redo,
+            Key::Ctrl('s') => Action::Write,
+            Key::Ctrl(_) | Key::Char('z') => {
+                self.pos = start;
+                self.scroll()?
+            },
+            Key::Char('~') => Action::ToggleCase,
+            Key::Char('J') => Action::Join,
+            Key::Char('r') => Action::Replace(self.next_char()?),
+            Key::Char('x') | Key::Delete => Action::Operate(Operator::Delete, Target::Motion(Motion::Right)),
+            Key::Char('X') => Action::Operate(Operator::Delete, Target::Motion(Motion::Left)),
+            Key::Char('D') => Action::Operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+            Key::Char('C') => Action::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+            Key::Char('s') => Action::Operate(Operator::Change, Target::Motion(Motion::Right)),
+            Key::Char('S') => Action::Operate(Operator::Change, Target::Line),
+            Key::Char('Y') => Action::Operate(Operator::Yank, Target::Line),
+            Key::Char('g') if self.peek() == Some(Key::Char('-')) => {
+                self.pos += 1;
+                Action::EarlierState
+            },
+            Key::Char('g') if self.peek() == Some(Key::Char('+')) => {
+                self.pos += 1;
+                Action::LaterState
+            },
+            Key::Char('g') if self.peek() == Some(Key::Char('v')) => {
+                self.pos += 1;
+                Action::Reselect
+            },
+            _ => {
+                self.pos = start;
+
+                match self.operator()? {
+                    Some(operator) => {
+                        let operator_keys = &self.keys[start..self.pos];
+                        count = mul(count, self.count());
+
+                        let target = self.target(operator_keys)?;
+                        Action::Operate(operator, target)
+                    },
+                    None => Action::Move(self.motion()?),
+                }
+            },
+        };
+
+        Ok(Command {
+            count,
+            register,
+            action,
+        })
+    }
+
+    fn visual_command(&mut self) -> Result<Command, Incomplete> {
+        let (count, register) = self.prefix()?;
+
+        let selection = |operator| Action::Operate(operator, Target::Selection { lines: false });
+        let lines = |operator| Action::Operate(operator, Target::Selection { lines: true });
+
+        let start = self.pos;
+        let action = match self.next()? {
+            Key::Char('v') => Action::Visual(VisualKind::Char),
+            Key::Char('V') => Action::Visual(VisualKind::Line),
+            Key::Ctrl('v') => Action::Visual(VisualKind::Block),
+            Key::Char('o') => Action::SwapEnds { column: false },
+            Key::Char('O') => Action::SwapEnds { column: true },
+            Key::Char(c @ ('i' | 'a')) => Action::Select(self.object(c == 'a')?),
+            Key::Ctrl('s') => Action::Write,
+            Key::Char(':') => Action::Ex,
+            Key::Ctrl(_) | Key::Char('z') => {
+                self.pos = start;
+                self.scroll()?
+            },
+            Key::Char('J') => Action::Join,
+            Key::Char('r') => Action::Replace(self.next_char()?),
+            Key::Char('p') => Action::Put { before: false, indent: false },
+            Key::Char('P') => Action::Put { before: true, indent: false },
+            Key::Char('x') | Key::Delete => selection(Operator::Delete),
+            Key::Char('s') => selection(Operator::Change),
+            Key::Char('~') => selection(Operator::ToggleCase),
+            Key::Char('u') => selection(Operator::Lowercase),
+            Key::Char('U') => selection(Operator::Uppercase),
+            Key::Char('X' | 'D') => lines(Operator::Delete),
+            Key::Char('C' | 'S' | 'R') => lines(Operator::Change),
+            Key::Char('Y') => lines(Operator::Yank),
+            Key::Char('g') if self.peek() == Some(Key::Char('v')) => {
+                self.pos += 1;
+                Action::Reselect
+            },
+            _ => {
+                self.pos = start;
+
+                match self.operator()? {
+                    Some(operator) => selection(operator),
+                    None => Action::Move(self.motion()?),
+                }
+            },
+        };
+
+        Ok(Command {
+            count,
+            register,
+            action,
+        })
+    }
+
+    /// Parses a command scrolling the text, which works the same in normal and visual mode.
+    fn scroll(&mut self) -> Result<Action, Incomplete> {
+        let action = match self.next()? {
+            Key::Ctrl('e') => Action::Scroll(Scroll::Lines { down: true }),
+            Key::Ctrl('y') => Action::Scroll(Scroll::Lines { down: false }),
+            Key::Ctrl('d') => Action::Scroll(Scroll::Half { down: true }),
+            Key::Ctrl('u') => Action::Scroll(Scroll::Half { down: false }),
+            Key::Ctrl('f') => Action::Scroll(Scroll::Page { down: true }),
+            Key::Ctrl('b') => Action::Scroll(Scroll::Page { down: false }),
+            Key::Char('z') => match self.next()? {
+                Key::Char('t') => Action::Align(Align::Top),
+                Key::Char('z') => Action::Align(Align::Center),
+                Key::Char('b') => Action::Align(Align::Bottom),
+                _ => return Err(Incomplete::Invalid),
+            },
+            _ => return Err(Incomplete::Invalid),
+        };
+
+        Ok(action)
+    }
+
+    /// Parses an operator, returning `None` without consuming any key when there is none.
+    fn operator(&mut self) -> Result<Option<Operator>, Incomplete> {
+        let start = self.pos;
+
+        let operator = match self.next()? {
+            Key::Char('d') => Operator::Delete,
+            Key::Char('c') => Operator::Change,
+            Key::Char('y') => Operator::Yank,
+            Key::Char('>') => Operator::Indent,
+            Key::Char('<') => Operator::Dedent,
+            Key::Char('!') => Operator::Filter,
+            Key::Char('g') => match self.next()? {
+                Key::Char('u') => Operator::Lowercase,
+                Key::Char('U') => Operator::Uppercase,
+                Key::Char('~') => Operator::ToggleCase,
+                _ => {
+                    self.pos = start;
+                    return Ok(None);
+                },
+            },
+            _ => {
+                self.pos = start;
+                return Ok(None);
+            },
+        };
+
+        Ok(Some(operator))
+    }
+
+    /// Parses what an operator typed with `operator_keys` acts on. Typing the operator again, or
+    /// just its last key for the ones prefixed by `g`, makes it act on whole lines.
+    fn target(&mut self, operator_keys: &[Key]) -> Result<Target, Incomplete> {
+        let last = operator_keys[operator_keys.len() - 1];
+        if self.peek() == Some(last) {
+            self.pos += 1;
+            return Ok(Target::Line);
+        }
+
+        let rest = &self.keys[self.pos..];
+        if operator_keys.len() > 1 && operator_keys.starts_with(rest) && rest.len() < operator_keys.len() {
+            return Err(Incomplete::Pending);
+        }
+
+        if rest.starts_with(operator_keys) {
+            self.pos += operator_keys.len();
+            return Ok(Target::Line);
+        }
+
+        if let Some(Key::Char(c @ ('i' | 'a'))) = self.peek() {
+            self.pos += 1;
+            return Ok(Target::Object(self.object(c == 'a')?));
+        }
+
+        Ok(Target::Motion(self.motion()?))
+    }
+
+    /// Parses the kind of a text object, after its `i` or `a`.
+    fn object(&mut self, around: bool) -> Result<TextObject, Incomplete> {
+        let kind = match self.next_char()? {
+            'w' => ObjectKind::Word { big: false },
+            'W' => ObjectKind::Word { big: true },
+            's' => ObjectKind::Sentence,
+            'p' => ObjectKind::Paragraph,
+            '(' | ')' | 'b' => ObjectKind::Pair('(', ')'),
+            '{' | '}' | 'B' => ObjectKind::Pair('{', '}'),
+            '[' | ']' => ObjectKind::Pair('[', ']'),
+            '<' | '>' => ObjectKind::Pair('<', '>'),
+            c @ ('"' | '\'' | '`') => ObjectKind::Quote(c),
+            't' => ObjectKind::Tag,
+            _ => return Err(Incomplete::Invalid),
+        };
+
+        Ok(TextObject { kind, around })
+    }
+
+    fn motion(&mut self) -> Result<Motion, Incomplete> {
+        let motion = match self.next()? {
+            Key::Char('h') | Key::Left | Key::Backspace => Motion::Left,
+            Key::Char('l') | Key::Right | Key::Char(' ') => Motion::Right,
+            Key::Char('k') | Key::Up => Motion::Up,
+            Key::Char('j') | Key::Down => Motion::Down,
+            Key::Char('0') => Motion::LineStart,
+            Key::Char('^') => Motion::FirstNonBlank,
+            Key::Char('$') => Motion::LineEnd,
+            Key::Char('|') => Motion::Column,
+            Key::Char('+') | Key::Enter => Motion::NextLine,
+            Key::Char('-') => Motion::PrevLine,
+            Key::Char('G') => Motion::LastLine,
+            Key::Char('H') => Motion::ScreenTop,
+            Key::Char('M') => Motion::ScreenMiddle,
+            Key::Char('L') => Motion::ScreenBottom,
+            Key::Char('w') => Motion::NextWord { big: false },
+            Key::Char('W') => Motion::NextWord { big: true },
+            Key::Char('b') => Motion::PrevWord { big: false },
+            Key::Char('B') => Motion::PrevWord { big: true },
+            Key::Char('e') => Motion::NextWordEnd { big: false },
+            Key::Char('E') => Motion::NextWordEnd { big: true },
+            Key::Char(')') => Motion::NextSentence,
+            Key::Char('(') => Motion::PrevSentence,
+            Key::Char('}') => Motion::NextParagraph,
+            Key::Char('{') => Motion::PrevParagraph,
+            Key::Char('n') => Motion::SearchNext { reverse: false },
+            Key::Char('N') => Motion::SearchNext { reverse: true },
+            Key::Char('*') => Motion::SearchWord { forward: true },
+            Key::Char('#') => Motion::SearchWord { forward: false },
+            Key::Char('\'') => Motion::Mark { name: self.next_char()?, exact: false },
+            Key::Char('`') => Motion::Mark { name: self.next_char()?, exact: true },
+            Key::Char(';') => Motion::RepeatFind { reverse: false },
+            Key::Char(',') => Motion::RepeatFind { reverse: true },
+            Key::Char(c @ ('f' | 'F' | 't' | 'T')) => {
+                let find = Find {
+                    c: self.next_char()?,
+                    forward: c == 'f' || c == 't',
+                    till: c == 't' || c == 'T',
+                };
+
+                Motion::Find { find, again: false }
+            },
+            Key::Char('g') => match self.next()? {
+                Key::Char('g') => Motion::FirstLine,
+                Key::Char('e') => Motion::PrevWordEnd { big: false },
+                Key::Char('E') => Motion::PrevWordEnd { big: true },
+                Key::Char('j') => Motion::ScreenDown,
+                Key::Char('k') => Motion::ScreenUp,
+                Key::Char('0') => Motion::ScreenLineStart,
+                Key::Char('$') => Motion::ScreenLineEnd,
+                _ => return Err(Incomplete::Invalid),
+            },
+            _ => return Err(Incomplete::Invalid),
+        };
+
+        Ok(motion)
+    }
+}
+
+/// Multiplies two optional counts, where a missing count does not change the other one.
+fn mul(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.saturating_mul(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(keys: &str) -> Option<Command> {
+        match parse(&Key::parse(keys)) {
+            Parse::Done(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    fn action(keys: &str) -> Option<Action> {
+        command(keys).map(|command| command.action)
+    }
+
+    #[test]
+    fn operators_take_motions_objects_and_lines() {
+        assert_eq!(action("dw"), Some(Action::Operate(Operator::Delete, Target::Motion(Motion::NextWord { big: false }))));
+        assert_eq!(action("dd"), Some(Action::Operate(Operator::Delete, Target::Line)));
+        assert_eq!(action("gUU"), Some(Action::Operate(Operator::Uppercase, Target::Line)));
+        assert_eq!(action("g~g~"), Some(Action::Operate(Operator::ToggleCase, Target::Line)));
+
+        let object = TextObject { kind: ObjectKind::Pair('(', ')'), around: false };
+        assert_eq!(action("ci("), Some(Action::Operate(Operator::Change, Target::Object(object))));
+        assert_eq!(action("yaW"), Some(Action::Operate(Operator::Yank, Target::Object(TextObject { kind: ObjectKind::Word { big: true }, around: true }))));
+    }
+
+    #[test]
+    fn counts_multiply_and_registers_come_first() {
+        let delete = command("\"a2d3w").unwrap();
+        assert_eq!(delete.register, Some('a'));
+        assert_eq!(delete.count, Some(6));
+
+        assert_eq!(command("dw").unwrap().count, None);
+        assert_eq!(command("2\"b5j").unwrap().count, Some(10));
+    }
+
+    #[test]
+    fn incomplete_and_invalid_keys() {
+        assert!(matches!(parse(&Key::parse("d")), Parse::Pending));
+        assert!(matches!(parse(&Key::parse("2\"a")), Parse::Pending));
+        assert!(matches!(parse(&Key::parse("di")), Parse::Pending));
+        assert!(matches!(parse(&Key::parse("dy")), Parse::Invalid));
+    }
+}
@@ -0,0 +1,99 @@
+use super::{Editor, Mode, ex, key::Key, search::Pattern};
+
+impl Editor {
+    pub(super) fn command_mode(&mut self, key: Key) {
+        self.record_insert(key);
+
+        // Any key other than the ones going through the candidates accepts the one shown
+        if !matches!(key, Key::Tab | Key::Ctrl('n' | 'p')) {
+            self.completion = None;
+        }
+
+        match key {
+            Key::Esc => {
+                self.cancel_command();
+            },
+            Key::Backspace if self.prompt.text.is_empty() => {
+                self.cancel_command();
+            },
+            Key::Enter => {
+                self.revert_preview();
+                self.set_mode(Mode::Normal);
+
+                let text = self.prompt.text.clone();
+                if let Err(e) = self.prompt_history.add_command(&text) {
+                    self.message = Some(e);
+                }
+
+                if let Err(e) = self.run_ex(&text) {
+                    self.message = Some(e);
+                }
+
+                if matches!(self.mode, Mode::Normal) {
+                    self.clamp_normal();
+                    self.cx_keep = self.cx;
+                }
+            },
+            Key::Tab | Key::Ctrl('n') => {
+                self.complete(true);
+                self.preview_command();
+            },
+            Key::Ctrl('p') => {
+                self.complete(false);
+                self.preview_command();
+            },
+            Key::Up | Key::Down => {
+                if self.prompt.browse(&self.prompt_history.commands, key == Key::Up) {
+                    self.preview_command();
+                }
+            },
+            key => {
+                if self.prompt.edit(key) {
+                    self.preview_command();
+                }
+            },
+        }
+    }
+
+    /// Starts typing a command with `:`. In visual mode, the command starts with the range of
+    /// the lines selected, while a count makes it start with the range of as many lines from
+    /// the cursor's.
+    pub(super) fn start_command(&mut self, count: Option<usize>) {
+        let range = match (self.mode, count) {
+            (Mode::Visual(_), _) => "'<,'>".to_string(),
+            (_, Some(1)) => ".".to_string(),
+            (_, Some(n)) => format!(".,.+{}", n - 1),
+            (_, None) => String::new(),
+        };
+
+        self.exit_visual();
+        self.prompt.set(&range);
+        self.set_mode(Mode::Command);
+    }
+
+    /// Shows what the command typed so far would do, which only substitutions do.
+    fn preview_command(&mut self) {
+        self.revert_preview();
+        self.highlight = self.last_search.as_ref().and_then(|search| Pattern::new(&search.pattern));
+
+        let text = self.prompt.text.clone();
+        let Ok(ex) = ex::parse(&text) else {
+            return;
+        };
+
+        if !matches!(ex.name, "s" | "substitute") {
+            return;
+        }
+
+        if let Ok((start, end)) = self.resolve_range(ex.range.as_ref()) {
+            self.preview_substitution(start, end, ex.args);
+        }
+    }
+
+    fn cancel_command(&mut self) {
+        self.revert_preview();
+        self.highlight = self.last_search.as_ref().and_then(|search| Pattern::new(&search.pattern));
+
+        self.set_mode(Mode::Normal);
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs;
+
+use super::{Editor, ex, options::OPTIONS};
+
+/// The candidates for completing the word before the cursor in the command being typed, which
+/// Tab goes through.
+pub struct Completion {
+    /// Where the word being completed starts in the prompt, in characters.
+    start: usize,
+    /// The word as typed, which is shown again after going through every candidate.
+    typed: String,
+    candidates: Vec<String>,
+    /// The candidate shown, or `None` while the word as typed is shown.
+    selected: Option<usize>,
+}
+
+impl Editor {
+    /// Completes the word before the cursor with its next candidate, or its previous one when
+    /// `forward` is `false`.
+    pub(super) fn complete(&mut self, forward: bool) {
+        if self.completion.is_none() {
+            let before: String = self.prompt.text.chars().take(self.prompt.cursor).collect();
+            let (start, candidates) = self.candidates(&before);
+
+            match candidates.len() {
+                0 => return,
+                // A single candidate is accepted right away, so Tab completes what comes after it
+                1 => {
+                    self.prompt.replace(start, &candidates[0]);
+                    return;
+                },
+                _ => { },
+            }
+
+            self.completion = Some(Completion {
+                start,
+                typed: before.chars().skip(start).collect(),
+                candidates,
+                selected: None,
+            });
+        }
+
+        let Some(completion) = &mut self.completion else {
+            return;
+        };
+
+        let len = completion.candidates.len();
+        completion.selected = match (completion.selected, forward) {
+            (None, true) => Some(0),
+            (None, false) => Some(len - 1),
+            (Some(n), true) if n + 1 < len => Some(n + 1),
+            (Some(n), false) if n > 0 => Some(n - 1),
+            (Some(_), _) => None,
+        };
+
+        let text = match completion.selected {
+            Some(n) => &completion.candidates[n],
+            None => &completion.typed,
+        };
+
+        self.prompt.replace(completion.start, text);
+    }
+
+    /// Returns the candidates going through the wildmenu, with the one shown.
+    pub fn wildmenu(&self) -> Option<(&[String], Option<usize>)> {
+        let completion = self.completion.as_ref()?;
+        Some((&completion.candidates, completion.selected))
+    }
+
+    /// Returns where the word being completed in `text` starts, in characters, with its
+    /// candidates: the names of the commands, the files for `:e` and `:w`, the themes for
+    /// `:colorscheme` and the options for `:set`.
+    fn candidates(&self, text: &str) -> (usize, Vec<String>) {
+        let Ok(ex) = ex::parse(text) else {
+            return (0, vec![]);
+        };
+
+        let chars = |s: &str| text.chars().count() - s.chars().count();
+
+        // The name is still being typed
+        if !ex.bang && ex.args.is_empty() {
+            let mut names: Vec<String> = ex::COMMANDS
+                .iter()
+                .map(|(command, _)| command.to_string())
+                .filter(|command| command.starts_with(ex.name))
+                .collect();
+            names.dedup();
+
+            return (chars(ex.name), names);
+        }
+
+        let Some(name) = ex::command_name(ex.name) else {
+            return (0, vec![]);
+        };
+
+        // Only the last word of the arguments is completed
+        let word = ex.args.rsplit(char::is_whitespace).next().unwrap_or_default();
+        let start = chars(word);
+
+        let candidates = match name {
+            "edit" | "write" | "wq" | "xit" => files(word),
+            "colorscheme" => self.themes
+                .iter()
+                .filter(|theme| theme.starts_with(word))
+                .cloned()
+                .collect(),
+            "set" => OPTIONS
+                .iter()
+                .map(|(option, _)| option.to_string())
+                .filter(|option| option.starts_with(word))
+                .collect(),
+            _ => vec![],
+        };
+
+        (start, candidates)
+    }
+}
+
+/// Returns the paths of the files starting with `path`, with a `/` after the directories. Hidden
+/// files are left out unless `path` names one.
+fn files(path: &str) -> Vec<String> {
+    let (dir, name) = match path.rfind('/') {
+        Some(n) => path.split_at(n + 1),
+        None => ("", path),
+    };
+
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return vec![];
+    };
+
+    let mut files: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file = entry.file_name().into_string().ok()?;
+            if !file.starts_with(name) || (file.starts_with('.') && !name.starts_with('.')) {
+                return None;
+            }
+
+            let slash = if entry.file_type().ok()?.is_dir() { "/" } else { "" };
+            Some(format!("{dir}{file}{slash}"))
+        })
+        .collect();
+
+    files.sort();
+    files
+}
@@ -0,0 +1,719 @@
+use std::path::Path;
+
+use super::{Editor, Mode, file::File, key::Key, operator::Operator, register::Registers, search::Pattern};
+
+/// The commands that can be typed after `:`, with how many of their first letters are enough to
+/// type them, like `:d` for `:delete` or `:norm` for `:normal`.
+pub const COMMANDS: &[(&str, usize)] = &[
+    ("colorscheme", 4),
+    ("copy", 2),
+    ("delete", 1),
+    ("earlier", 2),
+    ("edit", 1),
+    ("global", 1),
+    ("later", 3),
+    ("move", 1),
+    ("nohlsearch", 3),
+    ("normal", 4),
+    ("quit", 1),
+    ("set", 2),
+    ("substitute", 1),
+    ("t", 1),
+    ("vglobal", 1),
+    ("wq", 2),
+    ("write", 1),
+    ("xit", 1),
+    ("yank", 1),
+];
+
+/// Returns the full name of the command typed as `name`, if there is one.
+pub fn command_name(name: &str) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .find(|(command, min)| name.len() >= *min && command.starts_with(name))
+        .map(|(command, _)| *command)
+}
+
+/// A line given to an ex command.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Address {
+    /// `.`, the line of the cursor.
+    Current,
+    /// `$`, the last line.
+    Last,
+    /// A line number, counting from `1`. Line `0` comes before the first line, like in `:m0`.
+    Line(usize),
+    /// `'x`, the line of the mark `x`.
+    Mark(char),
+    /// `/pattern/` and `?pattern?`, the next or previous line matching `pattern` from the
+    /// cursor's. An empty pattern is the last search.
+    Search { pattern: String, forward: bool },
+    /// An address followed by `+n` or `-n`, which are relative to the cursor's line when alone.
+    Offset(Box<Address>, isize),
+}
+
+/// The lines an ex command acts on, like `3,$` or `%`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LineRange {
+    pub start: Address,
+    pub end: Address,
+}
+
+/// A command typed after `:`, following `[range]name[!][args]`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Ex<'a> {
+    pub range: Option<LineRange>,
+    pub name: &'a str,
+    /// Whether the name is followed by `!`, which forces commands like `:q!`.
+    pub bang: bool,
+    /// Everything typed after the name.
+    pub args: &'a str,
+}
+
+/// Parses a command typed after `:`.
+///
+/// ### Example
+/// ```
+/// let ex = parse("%s/a/b/g").unwrap();
+///
+/// assert_eq!(ex.range, Some(LineRange { start: Address::Line(1), end: Address::Last }));
+/// assert_eq!(ex.name, "s");
+/// assert_eq!(ex.args, "/a/b/g");
+/// ```
+pub fn parse(text: &str) -> Result<Ex<'_>, String> {
+    let mut parser = Parser { text, pos: 0 };
+
+    let range = parser.range()?;
+    let rest = &text[parser.pos..];
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+
+    let (name, rest) = rest.split_at(name_len);
+    let args = rest.strip_prefix('!');
+
+    Ok(Ex {
+        range,
+        name,
+        bang: args.is_some(),
+        args: args.unwrap_or(rest),
+    })
+}
+
+/// Parses an address alone, like the one `:m` and `:t` take.
+pub fn parse_address(text: &str) -> Result<Address, String> {
+    let mut parser = Parser { text: text.trim(), pos: 0 };
+
+    let address = parser.address()?.ok_or("Invalid address")?;
+    if parser.pos < parser.text.len() {
+        return Err("Trailing characters".to_string());
+    }
+
+    Ok(address)
+}
+
+/// Parses the register and the count given to a command like `:d` and `:y`. Like in vim, a digit
+/// starts the count rather than naming a register.
+pub fn parse_register_count(text: &str) -> Result<(Option<char>, Option<usize>), String> {
+    let mut parser = Parser { text: text.trim(), pos: 0 };
+
+    let register = match parser.peek() {
+        Some(c) if c.is_ascii_digit() => None,
+        Some(c) if Registers::is_valid(c) => {
+            parser.pos += 1;
+            Some(c)
+        },
+        Some(_) => return Err(format!("Invalid register: {text}")),
+        None => None,
+    };
+
+    parser.skip_blanks();
+    let count = parser.number();
+
+    if count == Some(0) {
+        return Err("Positive count required".to_string());
+    }
+    if parser.pos < parser.text.len() {
+        return Err("Trailing characters".to_string());
+    }
+
+    Ok((register, count))
+}
+
+/// Splits `text` at the first `delimiter` that is not escaped, unescaping the delimiters before
+/// it. Returns the text after the delimiter too, if there is one.
+pub fn split_delimited(text: &str, delimiter: char) -> (String, Option<&str>) {
+    let mut part = String::new();
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == delimiter => return (part, Some(&text[i + c.len_utf8()..])),
+            '\\' => match chars.next() {
+                Some((_, c)) if c == delimiter => part.push(c),
+                Some((_, c)) => {
+                    part.push('\\');
+                    part.push(c);
+                },
+                None => part.push('\\'),
+            },
+            c => part.push(c),
+        }
+    }
+
+    (part, None)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    /// The byte offset of the next character to parse.
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_blanks(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let len = self.text[self.pos..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.text.len() - self.pos);
+
+        let n = self.text[self.pos..self.pos + len].parse().ok()?;
+        self.pos += len;
+
+        Some(n)
+    }
+
+    fn range(&mut self) -> Result<Option<LineRange>, String> {
+        self.skip_blanks();
+
+        if self.peek() == Some('%') {
+            self.pos += 1;
+            return Ok(Some(LineRange { start: Address::Line(1), end: Address::Last }));
+        }
+
+        let Some(start) = self.address()? else {
+            return Ok(None);
+        };
+
+        self.skip_blanks();
+        if self.peek() != Some(',') {
+            return Ok(Some(LineRange { end: start.clone(), start }));
+        }
+
+        self.pos += 1;
+        self.skip_blanks();
+
+        let end = self.address()?.ok_or("Invalid range")?;
+        Ok(Some(LineRange { start, end }))
+    }
+
+    /// Parses an address, returning `None` without consuming anything when there is none.
+    fn address(&mut self) -> Result<Option<Address>, String> {
+        let address = match self.peek() {
+            Some('.') => {
+                self.pos += 1;
+                Address::Current
+            },
+            Some('$') => {
+                self.pos += 1;
+                Address::Last
+            },
+            Some('\'') => {
+                self.pos += 1;
+                Address::Mark(self.next().ok_or("Invalid range")?)
+            },
+            Some(c @ ('/' | '?')) => {
+                self.pos += 1;
+
+                let (pattern, rest) = split_delimited(&self.text[self.pos..], c);
+                self.pos = rest.map_or(self.text.len(), |rest| self.text.len() - rest.len());
+
+                Address::Search { pattern, forward: c == '/' }
+            },
+            Some(c) if c.is_ascii_digit() => {
+                Address::Line(self.number().ok_or("Invalid range")?)
+            },
+            // An offset alone is relative to the cursor's line
+            Some('+' | '-') => Address::Current,
+            _ => return Ok(None),
+        };
+
+        let mut offset: isize = 0;
+        while let Some(c @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+
+            let n = match self.peek() {
+                Some(c) if c.is_ascii_digit() => self.number().and_then(|n| isize::try_from(n).ok()).ok_or("Invalid range")?,
+                _ => 1,
+            };
+
+            offset = if c == '+' { offset.checked_add(n) } else { offset.checked_sub(n) }.ok_or("Invalid range")?;
+        }
+
+        match offset {
+            0 => Ok(Some(address)),
+            offset => Ok(Some(Address::Offset(Box::new(address), offset))),
+        }
+    }
+}
+
+impl Editor {
+    /// Runs a command typed after `:`.
+    pub fn run_ex(&mut self, text: &str) -> Result<(), String> {
+        let ex = parse(text)?;
+
+        let name = match ex.name {
+            "" => "",
+            name => command_name(name).ok_or_else(|| format!("Not an editor command: {text}"))?,
+        };
+
+        let range = ex.range.as_ref();
+        let args = ex.args.trim();
+
+        match name {
+            // With a range, `!` filters its lines through a command, otherwise it runs one
+            "" if ex.bang => match range {
+                Some(range) => {
+                    let (start, end) = self.resolve_range(Some(range))?;
+                    self.filter(start, end, args)
+                },
+                None => self.shell(args),
+            },
+            // A range alone goes to its last line
+            "" => {
+                if let Some(range) = range {
+                    self.cy = self.resolve_address(&range.end)?.min(self.content_lines());
+                    self.cx = self.first_non_blank(self.cy);
+                }
+
+                Ok(())
+            },
+            "write" => self.write_to(args),
+            "wq" => {
+                self.write_to(args)?;
+                self.quit = true;
+                Ok(())
+            },
+            "xit" => {
+                if self.dirty {
+                    self.write_to(args)?;
+                }
+
+                self.quit = true;
+                Ok(())
+            },
+            "quit" => {
+                if self.dirty && !ex.bang {
+                    return Err("No write since last change (add ! to override)".to_string());
+                }
+
+                self.quit = true;
+                Ok(())
+            },
+            "edit" => self.edit(args, ex.bang),
+            "delete" | "yank" => {
+                let (mut start, mut end) = self.resolve_range(range)?;
+                let (register, count) = parse_register_count(args)?;
+                let (cx, cy) = (self.cx, self.cy);
+
+                // A count takes that many lines from the last one of the range
+                if let Some(count) = count {
+                    start = end;
+                    end = (end + count - 1).min(self.content_lines());
+                }
+
+                let operator = if name == "delete" { Operator::Delete } else { Operator::Yank };
+                self.operate(operator, self.lines_range(start, end), true, register);
+
+                // Yanking leaves the cursor where it was
+                if operator == Operator::Yank {
+                    (self.cx, self.cy) = (cx, cy);
+                }
+
+                Ok(())
+            },
+            "move" => {
+                let (start, end) = self.resolve_range(range)?;
+                self.move_lines(start, end, &parse_address(args)?)
+            },
+            "copy" | "t" => {
+                let (start, end) = self.resolve_range(range)?;
+                self.copy_lines(start, end, &parse_address(args)?)
+            },
+            "normal" => {
+                match range {
+                    Some(range) => {
+                        let (start, end) = self.resolve_range(Some(range))?;
+
+                        for n in start..=end {
+                            if n > self.content_lines() {
+                                break;
+                            }
+
+                            (self.cx, self.cy) = (0, n);
+                            self.normal(ex.args.trim_start());
+                        }
+                    },
+                    None => self.normal(ex.args.trim_start()),
+                }
+
+                Ok(())
+            },
+            "global" | "vglobal" => {
+                let (start, end) = match range {
+                    Some(range) => self.resolve_range(Some(range))?,
+                    None => (0, self.content_lines()),
+                };
+
+                self.global(start, end, ex.args, ex.bang || name == "vglobal")
+            },
+            "substitute" => {
+                let (start, end) = self.resolve_range(range)?;
+                self.substitute(start, end, ex.args)
+            },
+            "set" => self.set_options(args),
+            "nohlsearch" => {
+                self.highlight = None;
+                Ok(())
+            },
+            "colorscheme" => {
+                if args.is_empty() {
+                    return Err("Argument required".to_string());
+                }
+
+                if !self.themes.iter().any(|theme| theme == args) {
+                    return Err(format!("Cannot find color scheme: {args}"));
+                }
+
+                self.colorscheme = Some(args.to_string());
+                Ok(())
+            },
+            "earlier" | "later" => {
+                let (n, unit) = args.split_at(args.find(|c: char| !c.is_ascii_digit()).unwrap_or(args.len()));
+                let n: u64 = if n.is_empty() { 1 } else { n.parse().map_err(|_| "Invalid argument")? };
+
+                // Times too far back or ahead just go to the oldest or the newest state
+                let secs = match unit {
+                    "" => None,
+                    "s" => Some(n),
+                    "m" => Some(n.saturating_mul(60)),
+                    "h" => Some(n.saturating_mul(60 * 60)),
+                    "d" => Some(n.saturating_mul(60 * 60 * 24)),
+                    _ => return Err(format!("Invalid argument: {args}")),
+                };
+
+                match (secs, name) {
+                    (Some(secs), "earlier") => self.earlier(secs),
+                    (Some(secs), _) => self.later(secs),
+                    (None, "earlier") => self.earlier_states(n as usize),
+                    (None, _) => self.later_states(n as usize),
+                }
+
+                Ok(())
+            },
+            _ => Err(format!("Not an editor command: {text}")),
+        }
+    }
+
+    /// Returns the first and last lines of `range`, or the line of the cursor when there is no
+    /// range.
+    pub(super) fn resolve_range(&self, range: Option<&LineRange>) -> Result<(usize, usize), String> {
+        let Some(range) = range else {
+            return Ok((self.cy, self.cy));
+        };
+
+        let start = self.resolve_address(&range.start)?.min(self.content_lines());
+        let end = self.resolve_address(&range.end)?.min(self.content_lines());
+
+        Ok((start.min(end), start.max(end)))
+    }
+
+    /// Returns the line of the buffer `address` points to, counting from `1`, so `0` is before
+    /// the first line.
+    fn resolve_line(&self, address: &Address) -> Result<usize, String> {
+        let line = match address {
+            Address::Current => self.cy + 1,
+            Address::Last => self.content_lines() + 1,
+            Address::Line(n) => *n,
+            Address::Mark(c @ ('<' | '>')) => {
+                let (visual, (_, cy)) = self.last_visual.ok_or("Mark not set")?;
+                let (_, anchor) = visual.anchor;
+
+                if *c == '<' { anchor.min(cy) + 1 } else { anchor.max(cy) + 1 }
+            },
+            Address::Mark(name) => self.mark(*name).ok_or("Mark not set")?.1 + 1,
+            Address::Search { pattern, forward } => {
+                let pattern = match pattern.as_str() {
+                    "" => self.last_search.as_ref().map(|search| search.pattern.as_str()).ok_or("No previous pattern")?,
+                    pattern => pattern,
+                };
+
+                let regex = Pattern::new(pattern).ok_or_else(|| format!("Invalid pattern: {pattern}"))?;
+                let lines = self.content_lines() + 1;
+
+                (1..=lines)
+                    .map(|step| if *forward { (self.cy + step) % lines } else { (self.cy + lines - step) % lines })
+                    .find(|&n| regex.regex().is_match(&self.buffer.line(n)))
+                    .ok_or_else(|| format!("Pattern not found: {pattern}"))?
+                    + 1
+            },
+            Address::Offset(address, offset) => {
+                let line = self.resolve_line(address)?;
+                line.checked_add_signed(*offset).ok_or("Invalid range")?
+            },
+        };
+
+        if line > self.content_lines() + 1 {
+            return Err("Invalid range".to_string());
+        }
+
+        Ok(line)
+    }
+
+    /// Returns the line of the buffer `address` points to.
+    fn resolve_address(&self, address: &Address) -> Result<usize, String> {
+        self.resolve_line(address).map(|line| line.saturating_sub(1))
+    }
+
+    /// `:w [path]`, writes the content of the editor to its file, or to `path`. The editor takes
+    /// `path` as its file when it has none yet.
+    fn write_to(&mut self, path: &str) -> Result<(), String> {
+        if path.is_empty() {
+            return self.save();
+        }
+
+        match &self.file {
+            Some(file) if file.path != Path::new(path) => File::new(Path::new(path)).write(&self.buffer),
+            _ => {
+                self.file = Some(File::new(Path::new(path)));
+                self.save()
+            },
+        }
+    }
+
+    /// `:e[!] [path]`, opens the file at `path`, or reads the file of the editor again. Changes
+    /// not written are only thrown away with `!`.
+    fn edit(&mut self, path: &str, bang: bool) -> Result<(), String> {
+        if self.dirty && !bang {
+            return Err("No write since last change (add ! to override)".to_string());
+        }
+
+        let path = match (path, &self.file) {
+            ("", Some(file)) => file.path.clone(),
+            ("", None) => return Err("No file name".to_string()),
+            (path, _) => Path::new(path).to_path_buf(),
+        };
+
+        self.open(&path)
+    }
+
+    /// `:[range]m {address}`, moves the lines `first..=last` below the line of `address`.
+    fn move_lines(&mut self, first: usize, last: usize, address: &Address) -> Result<(), String> {
+        let target = self.resolve_line(address)?;
+        if target > first && target <= last {
+            return Err("Cannot move a range of lines into itself".to_string());
+        }
+
+        let text = self.remove_lines(first, last);
+        let count = last - first + 1;
+
+        // The lines below the ones moved went up
+        let target = if target > last { target - count } else { target };
+        self.put_below(target, &text, count);
+
+        Ok(())
+    }
+
+    /// `:[range]t {address}`, copies the lines `first..=last` below the line of `address`.
+    fn copy_lines(&mut self, first: usize, last: usize, address: &Address) -> Result<(), String> {
+        let target = self.resolve_line(address)?;
+        let text = self.buffer.slice(self.lines_range(first, last)).to_string();
+
+        let text = if text.ends_with('\n') { text } else { text + "\n" };
+        self.put_below(target, &text, last - first + 1);
+
+        Ok(())
+    }
+
+    /// Puts the `count` lines of `text` below the line `target`, counting from `1`, leaving the
+    /// cursor on the last one.
+    fn put_below(&mut self, target: usize, text: &str, count: usize) {
+        match target {
+            0 => {
+                self.cy = 0;
+                self.put_lines(text, true);
+            },
+            target => {
+                self.cy = target - 1;
+                self.put_lines(text, false);
+            },
+        }
+
+        self.cy += count - 1;
+        self.cx = self.first_non_blank(self.cy);
+    }
+
+    /// `:normal {keys}`, types `keys` in normal mode. A command or an insert session left
+    /// unfinished by them is cancelled, as if `Esc` had been typed.
+    fn normal(&mut self, keys: &str) {
+        self.set_mode(Mode::Normal);
+        self.batch += 1;
+
+        for key in Key::parse(keys) {
+            self.key(key);
+        }
+
+        if self.mode != Mode::Normal || !self.cmd_stack.is_empty() {
+            self.key(Key::Esc);
+        }
+
+        self.batch -= 1;
+    }
+
+    /// `:[range]g/pattern/command`, runs `command` on each line of `first..=last` matching
+    /// `pattern`, or not matching it when `invert` is set, like `:g!` and `:v` do. Lines deleted
+    /// before their turn are skipped.
+    fn global(&mut self, first: usize, last: usize, args: &str, invert: bool) -> Result<(), String> {
+        if self.global_lines.is_some() {
+            return Err("Cannot do :global recursive".to_string());
+        }
+
+        let mut chars = args.chars();
+        let delimiter = chars.next().ok_or("Regular expression missing from :global")?;
+        if delimiter.is_alphanumeric() || delimiter.is_whitespace() || delimiter == '\\' {
+            return Err("Invalid delimiter".to_string());
+        }
+
+        let (pattern, command) = split_delimited(chars.as_str(), delimiter);
+        let command = command.unwrap_or("");
+
+        let pattern = match pattern.as_str() {
+            "" => self.last_search.as_ref().map(|search| search.pattern.clone()).ok_or("No previous pattern")?,
+            _ => pattern,
+        };
+
+        let regex = Pattern::new(&pattern).ok_or_else(|| format!("Invalid pattern: {pattern}"))?;
+        let lines: Vec<Option<usize>> = (first..=last)
+            .filter(|&n| regex.regex().is_match(&self.buffer.line(n)) != invert)
+            .map(Some)
+            .collect();
+
+        let forward = self.last_search.as_ref().is_none_or(|search| search.forward);
+        self.set_search(pattern.clone(), forward);
+
+        if lines.is_empty() {
+            return Err(format!("Pattern not found: {pattern}"));
+        }
+
+        self.global_lines = Some(lines);
+        self.batch += 1;
+
+        let mut result = Ok(());
+        let mut i = 0;
+
+        while let Some(line) = self.global_lines.as_ref().and_then(|lines| lines.get(i).copied()) {
+            i += 1;
+
+            let Some(line) = line else {
+                continue;
+            };
+
+            (self.cx, self.cy) = (0, line);
+            result = self.run_ex(command);
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.global_lines = None;
+        self.batch -= 1;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(text: &str) -> Option<LineRange> {
+        parse(text).unwrap().range
+    }
+
+    fn lines(start: Address, end: Address) -> Option<LineRange> {
+        Some(LineRange { start, end })
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(range("d"), None);
+        assert_eq!(range("%d"), lines(Address::Line(1), Address::Last));
+        assert_eq!(range("3d"), lines(Address::Line(3), Address::Line(3)));
+        assert_eq!(range(".,$d"), lines(Address::Current, Address::Last));
+        assert_eq!(range("'a, 'bd"), lines(Address::Mark('a'), Address::Mark('b')));
+
+        let offset = |address, n| Address::Offset(Box::new(address), n);
+        assert_eq!(range(".-1,+2d"), lines(offset(Address::Current, -1), offset(Address::Current, 2)));
+        assert_eq!(range("$--d"), lines(offset(Address::Last, -2), offset(Address::Last, -2)));
+
+        let search = |pattern: &str, forward| Address::Search { pattern: pattern.to_string(), forward };
+        assert_eq!(range("/a\\/b/,?c?d"), lines(search("a/b", true), search("c", false)));
+
+        assert!(parse("1,d").is_err());
+        assert!(parse(".+18446744073709551615d").is_err());
+        assert!(parse("+9223372036854775807+1d").is_err());
+        assert!(parse("-99999999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn names_and_arguments() {
+        let ex = parse("5,10s/a/b/g").unwrap();
+        assert_eq!((ex.name, ex.bang, ex.args), ("s", false, "/a/b/g"));
+
+        let ex = parse("q!").unwrap();
+        assert_eq!((ex.name, ex.bang, ex.args), ("q", true, ""));
+
+        assert_eq!(command_name("norm"), Some("normal"));
+        assert_eq!(command_name("no"), None);
+        assert_eq!(command_name("m"), Some("move"));
+    }
+
+    #[test]
+    fn addresses_registers_and_counts() {
+        assert_eq!(parse_address(" 0 "), Ok(Address::Line(0)));
+        assert_eq!(parse_address("1x"), Err("Trailing characters".to_string()));
+
+        assert_eq!(parse_register_count(""), Ok((None, None)));
+        assert_eq!(parse_register_count("a"), Ok((Some('a'), None)));
+        assert_eq!(parse_register_count("3"), Ok((None, Some(3))));
+        assert_eq!(parse_register_count("_ 12"), Ok((Some('_'), Some(12))));
+        assert!(parse_register_count("#").is_err());
+        assert!(parse_register_count("0").is_err());
+    }
+
+    #[test]
+    fn huge_numbers_are_errors_or_saturate() {
+        let mut editor = Editor::new("a\nb\nc");
+        for key in Key::parse(":.+18446744073709551615d\n:earlier 99999999999999999d\n:later 99999999999999999h\n") {
+            editor.key(key);
+        }
+
+        assert_eq!(editor.buffer.to_string(), "a\nb\nc");
+    }
+}
@@ -0,0 +1,191 @@
+use std::{fs, io::Write, path::{Path, PathBuf}};
+
+use super::{Editor, buffer::Buffer, history::History};
+
+/// The line ending used by a file on disk.
+///
+/// The buffer always separates lines with `'\n'`, so the line ending is only
+/// applied back when the file is written.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    #[inline]
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// A file opened in the editor.
+pub struct File {
+    pub path: PathBuf,
+    pub line_ending: LineEnding,
+    /// Whether the file ends with a line break. Like in vim, that line break is not part of the
+    /// buffer, otherwise it would show up as an empty line at the end of the file.
+    pub trailing_newline: bool,
+}
+
+impl File {
+    /// Describes a file that does not exist on disk yet.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line_ending: LineEnding::Lf,
+            trailing_newline: true,
+        }
+    }
+
+    /// Reads the file at `path`, returning it along with its text ready to be placed in the buffer.
+    /// When the file does not exist, an empty text is returned and the file is created on save.
+    pub fn read(path: &Path) -> Result<(Self, String), String> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok((Self::new(path), String::new()));
+            },
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        // Only a file ending every line with `\r\n` is converted. In a file mixing both, the `\r`
+        // stays in the lines that have it, so each line is written back with its own line ending.
+        let lf = data.matches('\n').count();
+        let line_ending = if lf > 0 && data.matches("\r\n").count() == lf {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        };
+
+        let mut text = match line_ending {
+            LineEnding::Lf => data,
+            LineEnding::CrLf => data.replace("\r\n", "\n"),
+        };
+
+        let trailing_newline = text.ends_with('\n');
+        if trailing_newline {
+            text.pop();
+        }
+
+        let file = Self {
+            path: path.to_path_buf(),
+            line_ending,
+            trailing_newline,
+        };
+
+        Ok((file, text))
+    }
+
+    /// Writes `buffer` to the file, restoring its line endings and trailing line break.
+    ///
+    /// The text is first written to a temporary file in the same directory, which is then renamed
+    /// over the original one, so the file is never left half written.
+    pub fn write(&self, buffer: &Buffer) -> Result<(), String> {
+        let mut data = buffer.to_string();
+        if self.trailing_newline {
+            data.push('\n');
+        }
+
+        if self.line_ending != LineEnding::Lf {
+            data = data.replace('\n', self.line_ending.as_str());
+        }
+
+        // A symlink is written through, so it keeps pointing to the file
+        let path = fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone());
+        let tmp_path = sibling(&path, ".echo~")?;
+
+        let write = || -> std::io::Result<()> {
+            let mut tmp = fs::File::create(&tmp_path)?;
+            tmp.write_all(data.as_bytes())?;
+            tmp.sync_all()?;
+
+            // Keep the permissions of the file being replaced.
+            if let Ok(metadata) = fs::metadata(&path) {
+                fs::set_permissions(&tmp_path, metadata.permissions())?;
+            }
+
+            fs::rename(&tmp_path, &path)
+        };
+
+        write().map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            format!("{}: {}", self.path.display(), e)
+        })
+    }
+
+    /// Returns the path of the undo file, which lives next to the file as a hidden file.
+    pub fn undo_path(&self) -> Result<PathBuf, String> {
+        sibling(&self.path, ".echo-undo")
+    }
+
+    /// Reads the undo history of the file, if it still belongs to the text of `buffer`.
+    pub fn read_history(&self, buffer: &Buffer) -> Option<History> {
+        let data = fs::read(self.undo_path().ok()?).ok()?;
+        History::deserialize(&data, buffer)
+    }
+
+    /// Writes the undo history of the file, marking it as belonging to the text of `buffer`.
+    pub fn write_history(&self, history: &History, buffer: &Buffer) -> Result<(), String> {
+        let path = self.undo_path()?;
+        fs::write(&path, history.serialize(buffer.hash()))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Returns the path of a hidden file next to the file at `path`, named after it with `suffix`.
+fn sibling(path: &Path, suffix: &str) -> Result<PathBuf, String> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("{}: not a file", path.display()))?;
+
+    let mut sibling = std::ffi::OsString::from(".");
+    sibling.push(name);
+    sibling.push(suffix);
+
+    Ok(path.with_file_name(sibling))
+}
+
+impl Editor {
+    /// Replaces the content of the editor with the file at `path`.
+    pub fn open(&mut self, path: &Path) -> Result<(), String> {
+        let (file, text) = File::read(path)?;
+
+        self.buffer = Buffer::new(&text);
+        self.history = file.read_history(&self.buffer).unwrap_or_default();
+        self.file = Some(file);
+        self.dirty = false;
+        self.marks.clear();
+
+        self.cx = 0;
+        self.cx_keep = 0;
+        self.cy = 0;
+        self.top = 0;
+        self.left = 0;
+
+        Ok(())
+    }
+
+    /// Writes the content of the editor back to its file.
+    pub fn save(&mut self) -> Result<(), String> {
+        let file = self.file.as_ref().ok_or("No file name")?;
+        file.write(&self.buffer)?;
+
+        self.dirty = false;
+
+        self.history.commit();
+        file.write_history(&self.history, &self.buffer)?;
+
+        Ok(())
+    }
+
+    /// Saves the content of the editor, reporting when it could not be written.
+    pub(super) fn write(&mut self) {
+        if let Err(e) = self.save() {
+            self.message = Some(e);
+        }
+    }
+}
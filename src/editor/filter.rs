@@ -0,0 +1,103 @@
+use std::{io::Write, process::{Command, Stdio}, thread};
+
+use super::{Editor, register::normalize_line_endings};
+
+/// Runs `command` with the shell of the user, giving it `input`, returning what it wrote to
+/// stdout and to stderr. A command exiting with an error fails with what it wrote to stderr.
+fn run_shell(command: &str, input: Option<String>) -> Result<(String, String), String> {
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+
+    let mut child = Command::new(&shell)
+        .arg("-c")
+        .arg(command)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Cannot run {shell}: {e}"))?;
+
+    // The input is written from another thread, so a command writing a lot before reading all of
+    // it does not wait on the editor forever.
+    let writer = input.zip(child.stdin.take()).map(|(input, mut stdin)| {
+        thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        })
+    });
+
+    let output = child.wait_with_output().map_err(|e| format!("Cannot run {command}: {e}"))?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).trim_end().to_string();
+
+    if !output.status.success() {
+        let status = match output.status.code() {
+            Some(code) => format!("Shell returned {code}"),
+            None => "Shell was killed".to_string(),
+        };
+
+        return Err(if stderr.is_empty() { status } else { format!("{status}: {stderr}") });
+    }
+
+    Ok((stdout, stderr))
+}
+
+impl Editor {
+    /// `:{range}!{command}`, replaces the lines `first..=last` with what `command` writes when
+    /// given them. When it fails, the lines are left as they were.
+    pub(super) fn filter(&mut self, first: usize, last: usize, command: &str) -> Result<(), String> {
+        if command.is_empty() {
+            return Err("Argument required".to_string());
+        }
+
+        let range = self.lines_range(first, last);
+        let text = self.buffer.slice(range.clone()).to_string();
+
+        let input = if text.ends_with('\n') { text.clone() } else { format!("{text}\n") };
+        let (output, stderr) = run_shell(command, Some(input))?;
+        let mut output = normalize_line_endings(&output);
+
+        if output.is_empty() {
+            self.remove_lines(first, last);
+        } else {
+            // The output takes the place of the lines, so it ends like they did: with the line
+            // break joining them to the next line, or without one at the end of the buffer
+            if !text.ends_with('\n') && output.ends_with('\n') {
+                output.pop();
+            } else if text.ends_with('\n') && !output.ends_with('\n') {
+                output.push('\n');
+            }
+
+            if output != text {
+                self.buffer_replace(range, &output);
+            }
+        }
+
+        self.cy = first.min(self.content_lines());
+        self.cx = self.first_non_blank(self.cy);
+
+        if !stderr.is_empty() {
+            self.message = Some(stderr);
+        }
+
+        Ok(())
+    }
+
+    /// `:!{command}`, runs `command`, showing what it writes.
+    pub(super) fn shell(&mut self, command: &str) -> Result<(), String> {
+        if command.is_empty() {
+            return Err("Argument required".to_string());
+        }
+
+        let (stdout, stderr) = run_shell(command, None)?;
+        let output = format!("{}\n{stderr}", stdout.trim_end());
+
+        if !output.trim().is_empty() {
+            self.message = Some(output.trim().to_string());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,634 @@
+use crate::util::usize_sub;
+
+use super::{Editor, buffer::Buffer};
+
+/// A primitive change made to the buffer.
+#[derive(Clone)]
+pub enum Edit {
+    /// `text` was inserted at the byte offset `pos`.
+    Insert { pos: usize, text: String },
+    /// `text` was removed from the byte offset `pos`.
+    Remove { pos: usize, text: String },
+}
+
+impl Edit {
+    fn apply(&self, buffer: &mut Buffer) {
+        match self {
+            Edit::Insert { pos, text } => buffer.insert(*pos, text),
+            Edit::Remove { pos, text } => {
+                buffer.remove(*pos..*pos + text.len());
+            },
+        }
+    }
+
+    pub(super) fn revert(&self, buffer: &mut Buffer) {
+        match self {
+            Edit::Insert { pos, text } => {
+                buffer.remove(*pos..*pos + text.len());
+            },
+            Edit::Remove { pos, text } => buffer.insert(*pos, text),
+        }
+    }
+
+    /// Whether the edit can be applied to `buffer`, or reverted from it when `revert` is set: the
+    /// text it inserts goes between two characters, and the text it removes is there.
+    fn fits(&self, buffer: &Buffer, revert: bool) -> bool {
+        match (self, revert) {
+            (Edit::Insert { pos, .. }, false) | (Edit::Remove { pos, .. }, true) => buffer.get_slice(*pos..*pos).is_some(),
+            (Edit::Insert { pos, text }, true) | (Edit::Remove { pos, text }, false) => pos
+                .checked_add(text.len())
+                .and_then(|end| buffer.get_slice(*pos..end))
+                .is_some_and(|removed| removed == text.as_str()),
+        }
+    }
+
+    /// Tries to merge `next` into this edit, which is the case when typing or deleting a run of
+    /// characters. Returns `false` when the edits are not contiguous.
+    fn merge(&mut self, next: &Edit) -> bool {
+        match (self, next) {
+            (Edit::Insert { pos, text }, Edit::Insert { pos: next_pos, text: next_text }) if *pos + text.len() == *next_pos => {
+                text.push_str(next_text);
+                true
+            },
+            // Deleting backwards, like backspace does.
+            (Edit::Remove { pos, text }, Edit::Remove { pos: next_pos, text: next_text }) if *next_pos + next_text.len() == *pos => {
+                text.insert_str(0, next_text);
+                *pos = *next_pos;
+                true
+            },
+            // Deleting forwards.
+            (Edit::Remove { pos, text }, Edit::Remove { pos: next_pos, text: next_text }) if *pos == *next_pos => {
+                text.push_str(next_text);
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+/// A group of edits that are undone and redone as a single step.
+pub struct Change {
+    pub edits: Vec<Edit>,
+    /// The cursor position, as `(cx, cy)`, before the first edit was made.
+    pub cursor: (usize, usize),
+}
+
+/// A state of the buffer in the undo tree, reached by applying `change` to the state of `parent`.
+struct Node {
+    parent: usize,
+    change: Change,
+    /// When the change was made, in seconds since the UNIX epoch.
+    time: u64,
+    /// The child that `redo` goes to, which is the last one created or undone from.
+    redo: Option<usize>,
+}
+
+/// The journal of every change made to the buffer, kept as a tree.
+///
+/// Each node is a state of the buffer. Undoing a change and then making a new one creates a
+/// branch instead of discarding the undone change, so every past state can still be reached by
+/// walking the tree, like [`History::goto`] does. Nodes are stored in the order they were
+/// created, which gives a chronological order over all states regardless of their branch.
+///
+/// Edits are recorded into a pending change until [`History::commit`] is called, so everything
+/// done by a single command, like a whole insert session, is undone at once.
+pub struct History {
+    /// Node `0` is the root, the state of the buffer before any change.
+    nodes: Vec<Node>,
+    /// The node matching the current state of the buffer.
+    current: usize,
+    pending: Option<Change>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node {
+                parent: 0,
+                change: Change { edits: vec![], cursor: (0, 0) },
+                time: now(),
+                redo: None,
+            }],
+            current: 0,
+            pending: None,
+        }
+    }
+}
+
+impl History {
+    /// Records an edit that has just been made to the buffer, with the cursor position before it.
+    pub fn record(&mut self, edit: Edit, cursor: (usize, usize)) {
+        let change = self.pending.get_or_insert_with(|| Change {
+            edits: vec![],
+            cursor,
+        });
+
+        let merged = change.edits
+            .last_mut()
+            .is_some_and(|last| last.merge(&edit));
+
+        if !merged {
+            change.edits.push(edit);
+        }
+    }
+
+    /// Closes the pending change, adding it to the tree as a child of the current state.
+    pub fn commit(&mut self) {
+        if let Some(change) = self.pending.take() {
+            self.nodes.push(Node {
+                parent: self.current,
+                change,
+                time: now(),
+                redo: None,
+            });
+
+            let node = self.nodes.len() - 1;
+            self.nodes[self.current].redo = Some(node);
+            self.current = node;
+        }
+    }
+
+    /// Reverts the last change from `buffer` and returns the cursor position before it was made.
+    pub fn undo(&mut self, buffer: &mut Buffer) -> Option<(usize, usize)> {
+        self.commit();
+
+        if self.current == 0 {
+            return None;
+        }
+
+        Some(self.revert(buffer))
+    }
+
+    /// Applies the last undone change to `buffer` again and returns the byte offset where it starts.
+    pub fn redo(&mut self, buffer: &mut Buffer) -> Option<usize> {
+        let node = self.nodes[self.current].redo?;
+
+        Some(self.apply(buffer, node))
+    }
+
+    /// Moves `buffer` to the state of `target`, going up the tree to the closest state shared with
+    /// the current one and then down to `target`. Returns the cursor position to use afterwards.
+    pub fn goto(&mut self, buffer: &mut Buffer, target: usize) -> Option<(usize, usize)> {
+        self.commit();
+
+        if target >= self.nodes.len() || target == self.current {
+            return None;
+        }
+
+        let path = self.path(target);
+        let mut cursor = None;
+
+        while !path.contains(&self.current) {
+            cursor = Some(self.revert(buffer));
+        }
+
+        let skip = path.iter().position(|n| *n == self.current).unwrap_or(0) + 1;
+        for node in path.iter().skip(skip) {
+            let pos = self.apply(buffer, *node);
+            cursor = Some(buffer.byte_to_cursor(pos));
+        }
+
+        cursor
+    }
+
+    /// Finds the state of the buffer `secs` seconds before the current one.
+    pub fn earlier(&self, secs: u64) -> usize {
+        let time = self.nodes[self.current].time.saturating_sub(secs);
+
+        // Nodes are in chronological order, so the last one made by then is the state at that time.
+        self.nodes[..=self.current]
+            .iter()
+            .rposition(|node| node.time <= time)
+            .unwrap_or(0)
+    }
+
+    /// Finds the state of the buffer `secs` seconds after the current one.
+    pub fn later(&self, secs: u64) -> usize {
+        let time = self.nodes[self.current].time.saturating_add(secs);
+
+        self.nodes
+            .iter()
+            .rposition(|node| node.time <= time)
+            .unwrap_or(0)
+            .max(self.current)
+    }
+
+    /// Returns the node of the current state, which chronologically follows the node before it.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Returns the node of the newest state.
+    #[inline]
+    pub fn last(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    /// Returns the nodes from the root down to `node`.
+    fn path(&self, mut node: usize) -> Vec<usize> {
+        let mut path = vec![node];
+        while node != 0 {
+            node = self.nodes[node].parent;
+            path.push(node);
+        }
+
+        path.reverse();
+        path
+    }
+
+    /// Reverts the change of the current node, moving to its parent.
+    fn revert(&mut self, buffer: &mut Buffer) -> (usize, usize) {
+        let node = &self.nodes[self.current];
+        for edit in node.change.edits.iter().rev() {
+            edit.revert(buffer);
+        }
+
+        let (parent, cursor) = (node.parent, node.change.cursor);
+        self.nodes[parent].redo = Some(self.current);
+        self.current = parent;
+
+        cursor
+    }
+
+    /// Applies the change of `node`, a child of the current node, and returns where it starts.
+    fn apply(&mut self, buffer: &mut Buffer, node: usize) -> usize {
+        let edits = &self.nodes[node].change.edits;
+        for edit in edits.iter() {
+            edit.apply(buffer);
+        }
+
+        let pos = edits
+            .iter()
+            .map(|edit| match edit {
+                Edit::Insert { pos, .. } | Edit::Remove { pos, .. } => *pos,
+            })
+            .min()
+            .unwrap_or(0);
+
+        self.nodes[self.current].redo = Some(node);
+        self.current = node;
+
+        pos
+    }
+}
+
+/// Serialization of the history into an undo file.
+///
+/// The file starts with a header holding the hash of the text the history belongs to, followed by
+/// one line per node and its edits. The text of each edit is written raw after the line describing
+/// it, prefixed by its length in bytes, so it may contain anything.
+///
+/// ```txt
+/// echo-undo 1
+/// <hash> <current>
+/// n <parent> <time> <cx> <cy> <redo> <edits>
+/// i <pos> <len>
+/// <text>
+/// ```
+impl History {
+    const MAGIC: &'static str = "echo-undo 1";
+
+    /// Serializes the history, marking it as belonging to the text whose hash is `hash`.
+    pub fn serialize(&self, hash: u64) -> Vec<u8> {
+        let mut out = format!("{}\n{:x} {}\n", Self::MAGIC, hash, self.current).into_bytes();
+
+        for node in self.nodes.iter() {
+            let redo = node.redo.map_or("-".to_string(), |n| n.to_string());
+            out.extend(format!(
+                "n {} {} {} {} {} {}\n",
+                node.parent, node.time, node.change.cursor.0, node.change.cursor.1, redo, node.change.edits.len(),
+            ).bytes());
+
+            for edit in node.change.edits.iter() {
+                let (kind, pos, text) = match edit {
+                    Edit::Insert { pos, text } => ('i', pos, text),
+                    Edit::Remove { pos, text } => ('r', pos, text),
+                };
+
+                out.extend(format!("{} {} {}\n", kind, pos, text.len()).bytes());
+                out.extend(text.bytes());
+                out.push(b'\n');
+            }
+        }
+
+        out
+    }
+
+    /// Restores a history serialized by [`History::serialize`], as long as it belongs to the text
+    /// of `buffer`. Returns `None` when the data is not valid or belongs to another text.
+    pub fn deserialize(data: &[u8], buffer: &Buffer) -> Option<Self> {
+        let mut reader = Reader { data, pos: 0 };
+
+        if reader.line()? != Self::MAGIC {
+            return None;
+        }
+
+        let header = reader.fields()?;
+        if u64::from_str_radix(header.first()?, 16).ok()? != buffer.hash() {
+            return None;
+        }
+
+        let current = header.get(1)?.parse().ok()?;
+        let mut nodes = vec![];
+
+        while !reader.is_empty() {
+            let fields = reader.fields()?;
+            if fields.len() != 7 || fields[0] != "n" {
+                return None;
+            }
+
+            let num = |i: usize| fields[i].parse::<usize>().ok();
+
+            let mut edits = vec![];
+            for _ in 0..num(6)? {
+                let fields = reader.fields()?;
+                let pos = fields.get(1)?.parse().ok()?;
+                let text = reader.text(fields.get(2)?.parse().ok()?)?;
+
+                edits.push(match *fields.first()? {
+                    "i" => Edit::Insert { pos, text },
+                    "r" => Edit::Remove { pos, text },
+                    _ => return None,
+                });
+            }
+
+            nodes.push(Node {
+                parent: num(1)?,
+                change: Change { edits, cursor: (num(3)?, num(4)?) },
+                time: fields[2].parse().ok()?,
+                redo: num(5),
+            });
+        }
+
+        // Parents are created before their children, so walking up from any node ends at the root
+        // instead of going around a cycle, and `redo` always goes to a child.
+        let valid = nodes.first().is_some_and(|root| root.parent == 0)
+            && current < nodes.len()
+            && nodes.iter().enumerate().all(|(n, node)| {
+                (n == 0 || node.parent < n)
+                    && node.redo.is_none_or(|redo| redo > n && nodes.get(redo).is_some_and(|child| child.parent == n))
+            });
+
+        if !valid {
+            return None;
+        }
+
+        let history = Self {
+            nodes,
+            current,
+            pending: None,
+        };
+
+        history.fits(buffer).then_some(history)
+    }
+
+    /// Whether every change of the tree can be made, starting from the current state being the
+    /// text of `buffer`, which is walked through on a copy of it.
+    fn fits(&self, buffer: &Buffer) -> bool {
+        let mut buffer = buffer.clone();
+
+        let mut node = self.current;
+        while node != 0 {
+            for edit in self.nodes[node].change.edits.iter().rev() {
+                if !edit.fits(&buffer, true) {
+                    return false;
+                }
+
+                edit.revert(&mut buffer);
+            }
+
+            node = self.nodes[node].parent;
+        }
+
+        let mut children = vec![vec![]; self.nodes.len()];
+        for (n, node) in self.nodes.iter().enumerate().skip(1) {
+            children[node.parent].push(n);
+        }
+
+        // Going down from the root, each change is made before the ones below it, and reverted
+        // once they are, which always fits as it removes what was just inserted and the other way
+        let mut stack: Vec<(usize, bool)> = children[0].iter().map(|&n| (n, true)).collect();
+        while let Some((node, down)) = stack.pop() {
+            let edits = &self.nodes[node].change.edits;
+
+            if !down {
+                edits.iter().rev().for_each(|edit| edit.revert(&mut buffer));
+                continue;
+            }
+
+            for edit in edits {
+                if !edit.fits(&buffer, false) {
+                    return false;
+                }
+
+                edit.apply(&mut buffer);
+            }
+
+            stack.push((node, false));
+            stack.extend(children[node].iter().map(|&n| (n, true)));
+        }
+
+        true
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn line(&mut self) -> Option<&'a str> {
+        let data = self.data;
+        let rest = data.get(self.pos..)?;
+        let len = rest.iter().position(|b| *b == b'\n')?;
+        self.pos += len + 1;
+
+        std::str::from_utf8(&rest[..len]).ok()
+    }
+
+    fn fields(&mut self) -> Option<Vec<&'a str>> {
+        Some(self.line()?.split(' ').collect())
+    }
+
+    /// Reads `len` bytes of text followed by a line break.
+    fn text(&mut self, len: usize) -> Option<String> {
+        let text = self.data.get(self.pos..self.pos + len)?;
+        if self.data.get(self.pos + len) != Some(&b'\n') {
+            return None;
+        }
+
+        self.pos += len + 1;
+        String::from_utf8(text.to_vec()).ok()
+    }
+}
+
+/// Returns the current time in seconds since the UNIX epoch.
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl Editor {
+    /// Undoes the last `n` changes, moving the cursor back to where it was before them.
+    pub fn undo(&mut self, n: usize) {
+        for _ in 0..n {
+            match self.history.undo(&mut self.buffer) {
+                Some((cx, cy)) => {
+                    self.cx = cx;
+                    self.cy = cy;
+                    self.dirty = true;
+                },
+                None => break,
+            }
+        }
+
+        self.clamp_cursor();
+    }
+
+    /// Redoes the last `n` undone changes, moving the cursor to where they start.
+    pub fn redo(&mut self, n: usize) {
+        for _ in 0..n {
+            match self.history.redo(&mut self.buffer) {
+                Some(pos) => {
+                    (self.cx, self.cy) = self.buffer.byte_to_cursor(pos);
+                    self.dirty = true;
+                },
+                None => break,
+            }
+        }
+
+        self.clamp_cursor();
+    }
+
+    /// Moves the content back to how it was `secs` seconds before its current state.
+    pub fn earlier(&mut self, secs: u64) {
+        let target = self.history.earlier(secs);
+        self.goto_state(target);
+    }
+
+    /// Moves the content forward to how it was `secs` seconds after its current state.
+    pub fn later(&mut self, secs: u64) {
+        let target = self.history.later(secs);
+        self.goto_state(target);
+    }
+
+    /// Moves `n` states back in time, regardless of the branch they are in.
+    pub fn earlier_states(&mut self, n: usize) {
+        let target = usize_sub(self.history.current(), n);
+        self.goto_state(target);
+    }
+
+    /// Moves `n` states forward in time, regardless of the branch they are in.
+    pub fn later_states(&mut self, n: usize) {
+        let target = self.history.current().saturating_add(n).min(self.history.last());
+        self.goto_state(target);
+    }
+
+    fn goto_state(&mut self, target: usize) {
+        if let Some((cx, cy)) = self.history.goto(&mut self.buffer, target) {
+            self.cx = cx;
+            self.cy = cy;
+            self.dirty = true;
+        }
+
+        self.clamp_cursor();
+    }
+
+    /// Keeps the cursor inside the buffer after its content was replaced.
+    fn clamp_cursor(&mut self) {
+        self.cy = self.cy.min(self.content_lines());
+        self.cx = self.cx.min(self.get_line_width(0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::key::Key;
+    use super::*;
+
+    /// Builds a history with a branch: "a" then "ab", undone back to "a" and changed to "ac".
+    fn branched() -> (History, Buffer) {
+        let mut buffer = Buffer::new("");
+        let mut history = History::default();
+
+        for (pos, text) in [(0, "a"), (1, "b")] {
+            buffer.insert(pos, text);
+            history.record(Edit::Insert { pos, text: text.to_string() }, (pos, 0));
+            history.commit();
+        }
+
+        history.undo(&mut buffer);
+        buffer.insert(1, "c\n");
+        history.record(Edit::Insert { pos: 1, text: "c\n".to_string() }, (1, 0));
+        history.commit();
+
+        (history, buffer)
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let (history, mut buffer) = branched();
+        let data = history.serialize(buffer.hash());
+
+        let mut restored = History::deserialize(&data, &buffer).unwrap();
+        assert_eq!(restored.serialize(buffer.hash()), data);
+        assert_eq!(restored.current(), 3);
+
+        restored.goto(&mut buffer, 2);
+        assert_eq!(buffer.to_string(), "ab");
+        restored.undo(&mut buffer);
+        restored.undo(&mut buffer);
+        assert_eq!(buffer.to_string(), "");
+    }
+
+    #[test]
+    fn deserialize_rejects_invalid_data() {
+        let (history, buffer) = branched();
+        let data = String::from_utf8(history.serialize(buffer.hash())).unwrap();
+
+        assert!(History::deserialize(data.as_bytes(), &Buffer::new("ac")).is_none());
+        assert!(History::deserialize(&data.as_bytes()[..data.len() - 2], &buffer).is_none());
+
+        // Node 1 and 2 being each other's parent would make walking up the tree loop forever
+        let mut nodes = 0;
+        let cycle: Vec<String> = data
+            .split('\n')
+            .map(|line| {
+                nodes += line.starts_with("n ") as usize;
+                if nodes == 2 && line.starts_with("n 0 ") { line.replacen("n 0 ", "n 2 ", 1) } else { line.to_string() }
+            })
+            .collect();
+        let cycle = cycle.join("\n");
+        assert!(History::deserialize(cycle.as_bytes(), &buffer).is_none());
+
+        // An edit going past the end of the text, or removing text that is not there
+        let past_end = data.replace("i 1 2\n", "i 9 2\n");
+        assert_ne!(past_end, data);
+        assert!(History::deserialize(past_end.as_bytes(), &buffer).is_none());
+
+        let mut other = History::default();
+        other.record(Edit::Insert { pos: 0, text: "x".to_string() }, (0, 0));
+        other.commit();
+        assert!(History::deserialize(&other.serialize(buffer.hash()), &buffer).is_none());
+    }
+
+    #[test]
+    fn later_states_stop_at_the_newest() {
+        let mut editor = Editor::new("");
+        for key in Key::parse("ix<Esc>u:later 99\n") {
+            editor.key(key);
+        }
+        assert_eq!(editor.buffer.to_string(), "x");
+
+        editor.later_states(usize::MAX);
+        assert_eq!(editor.buffer.to_string(), "x");
+    }
+}
@@ -1,47 +1,60 @@
-use super::{Editor, Mode};
+use crate::util::usize_sub;
+
+use super::{Editor, Mode, key::Key};
 
 impl Editor {
-    pub(super) fn insert_mode(&mut self, event: sdl2::event::Event) {
-        match event {
-            sdl2::event::Event::KeyDown { keycode, .. } => {
-                match keycode {
-                    // Quit insert mode
-                    Some(sdl2::keyboard::Keycode::Escape) => {
-                        self.mode = Mode::Normal
-                    },
-                    // ...
-                    Some(sdl2::keyboard::Keycode::Tab) => {
-                        self.insert("  ");
-                    },
-                    // Move cursor 1 position to the left
-                    Some(sdl2::keyboard::Keycode::Left) => {
-                        self.move_left(1);
-                    },
-                    // Move cursor 1 position downward
-                    Some(sdl2::keyboard::Keycode::Down) => {
-                        self.move_down(1);
-                    },
-                    // Move cursor 1 position upward
-                    Some(sdl2::keyboard::Keycode::Up) => {
-                        self.move_up(1);
-                    },
-                    // Move cursor 1 position to the right
-                    Some(sdl2::keyboard::Keycode::Right) => {
-                        self.move_right(1);
-                    },
-                    // Insert a new line
-                    Some(sdl2::keyboard::Keycode::Return) => {
-                        self.insert_line();
-                    },
-                    // Delete character under cursor
-                    Some(sdl2::keyboard::Keycode::Backspace) => {
-                        self.delete();
-                    },
-                    _ => { },
+    pub(super) fn insert_mode(&mut self, key: Key) {
+        self.record_insert(key);
+
+        match key {
+            // Quit insert mode, leaving the cursor over the last character typed
+            Key::Esc => {
+                self.finish_block_insert();
+
+                self.cx = usize_sub(self.cx, 1);
+                self.cx_keep = self.cx;
+                self.mode = Mode::Normal
+            },
+            // Write the content to its file
+            Key::Ctrl('s') => {
+                self.write();
+            },
+            // Paste the text of the system clipboard
+            Key::Ctrl('V') => {
+                if let Some(register) = self.register('+') {
+                    self.paste(&register.text);
                 }
             },
-            sdl2::event::Event::TextInput { text, .. } => {
-                self.insert(&text);
+            // ...
+            Key::Tab => {
+                self.insert("  ");
+            },
+            // Move cursor 1 position to the left
+            Key::Left => {
+                self.move_left(1);
+            },
+            // Move cursor 1 position downward
+            Key::Down => {
+                self.move_down(1);
+            },
+            // Move cursor 1 position upward
+            Key::Up => {
+                self.move_up(1);
+            },
+            // Move cursor 1 position to the right
+            Key::Right => {
+                self.move_right(1);
+            },
+            // Insert a new line
+            Key::Enter => {
+                self.insert_line();
+            },
+            // Delete character under cursor
+            Key::Backspace => {
+                self.delete();
+            },
+            Key::Char(c) => {
+                self.insert(c.encode_utf8(&mut [0; 4]));
             },
             _ => { },
         }
@@ -49,15 +62,7 @@ impl Editor {
 
     pub fn insert_line(&mut self) {
         let pos = self.cursor_pos();
-        self.content.insert_str(pos, "\n");
-
-        if self.cx < self.get_line_width(0) {
-            let chars_to_move = self.lines[self.cy] as usize - self.cx;
-            self.lines.insert(self.cy + 1, chars_to_move as u32);
-            self.lines[self.cy] -= chars_to_move as u32;
-        } else {
-            self.lines.insert(self.cy + 1, 0);
-        }
+        self.buffer_insert(pos, "\n");
 
         self.cy += 1;
         self.cx = 0;
@@ -65,12 +70,19 @@ impl Editor {
 
     pub fn insert(&mut self, text: &str) {
         let pos = self.cursor_pos();
+        self.buffer_insert(pos, text);
 
-        self.content.insert_str(pos, text);
+        // A combining mark or a joiner may merge the graphemes around the cursor, so the cursor
+        // goes after the text rather than past the graphemes in it.
+        (self.cx, self.cy) = self.buffer.byte_to_cursor(pos + text.len());
+    }
 
-        self.lines[self.cy] += text.len() as u32;
+    /// Inserts `text`, which may go through several lines, leaving the cursor after it.
+    pub fn paste(&mut self, text: &str) {
+        let pos = self.cursor_pos();
+        self.buffer_insert(pos, text);
 
-        self.cx += text.len();
+        (self.cx, self.cy) = self.buffer.byte_to_cursor(pos + text.len());
     }
 
     pub fn delete(&mut self) {
@@ -78,20 +90,19 @@ impl Editor {
             return;
         }
 
-        let removed_char = self.content.remove(self.cursor_pos() - 1);
-        if removed_char == '\n' {
-            self.cx = self.lines[self.cy - 1] as usize;
-            self.lines[self.cy - 1] += self.lines[self.cy];
-
-            self.lines.remove(self.cy);
-
+        let pos = self.cursor_pos();
+        if self.cx == 0 {
+            // Joining with the previous line, the cursor goes where the line break was.
+            self.cx = self.get_line_width(-1);
             self.cy -= 1;
 
+            self.buffer_remove(pos - 1..pos);
+
             return
         }
 
+        let start = self.buffer.grapheme_to_byte(self.cy, self.cx - 1);
+        self.buffer_remove(start..pos);
         self.cx -= 1;
-        self.lines[self.cy] -= 1;
     }
 }
-
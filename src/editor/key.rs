@@ -0,0 +1,147 @@
+use std::fmt::{Display, Formatter};
+
+use sdl2::{event::Event, keyboard::{Keycode, Mod}};
+
+/// A key pressed by the user, as understood by the editor modes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    /// A character typed as text.
+    Char(char),
+    /// A letter typed while holding control. The letter is uppercase when shift is held too.
+    Ctrl(char),
+    Esc,
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Key {
+    /// Translates an SDL event into the keys it represents.
+    ///
+    /// Printable characters arrive as `TextInput` events, so the only `KeyDown` events taken into
+    /// account are the ones for keys that do not produce text.
+    pub fn from_event(event: &Event) -> Vec<Key> {
+        match event {
+            Event::TextInput { text, .. } => {
+                text.chars().map(Key::Char).collect()
+            },
+            Event::KeyDown { keycode: Some(keycode), keymod, .. } => {
+                let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+                let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+
+                let key = match *keycode {
+                    Keycode::Escape => Key::Esc,
+                    Keycode::Return => Key::Enter,
+                    Keycode::Backspace => Key::Backspace,
+                    Keycode::Delete => Key::Delete,
+                    Keycode::Tab => Key::Tab,
+                    Keycode::Left => Key::Left,
+                    Keycode::Right => Key::Right,
+                    Keycode::Up => Key::Up,
+                    Keycode::Down => Key::Down,
+                    keycode if ctrl => {
+                        let name = keycode.name();
+                        let mut chars = name.chars();
+
+                        match (chars.next(), chars.next()) {
+                            (Some(c), None) if c.is_ascii_alphabetic() => {
+                                let c = if shift { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() };
+                                Key::Ctrl(c)
+                            },
+                            _ => return vec![],
+                        }
+                    },
+                    _ => return vec![],
+                };
+
+                vec![key]
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Parses keys written in the notation of [`Key`]'s `Display` implementation, like
+    /// `ciwfoo<Esc>`. Anything that is not a known key name is taken as typed, and line breaks
+    /// are taken as `Enter`, so yanked lines can be run as macros.
+    pub fn parse(text: &str) -> Vec<Key> {
+        let mut keys = vec![];
+        let mut rest = text;
+
+        while let Some(c) = rest.chars().next() {
+            let named = rest
+                .strip_prefix('<')
+                .and_then(|name| name.split_once('>'))
+                .and_then(|(name, after)| Some((Key::from_name(name)?, after)));
+
+            match named {
+                Some((key, after)) => {
+                    keys.push(key);
+                    rest = after;
+                },
+                None => {
+                    keys.push(if c == '\n' { Key::Enter } else { Key::Char(c) });
+                    rest = &rest[c.len_utf8()..];
+                },
+            }
+        }
+
+        keys
+    }
+
+    fn from_name(name: &str) -> Option<Key> {
+        let key = match name {
+            "lt" => Key::Char('<'),
+            "Esc" => Key::Esc,
+            "CR" => Key::Enter,
+            "BS" => Key::Backspace,
+            "Del" => Key::Delete,
+            "Tab" => Key::Tab,
+            "Left" => Key::Left,
+            "Right" => Key::Right,
+            "Up" => Key::Up,
+            "Down" => Key::Down,
+            _ => {
+                let (shift, c) = match name.strip_prefix("C-S-") {
+                    Some(c) => (true, c),
+                    None => (false, name.strip_prefix("C-")?),
+                };
+
+                let mut chars = c.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii_alphabetic() => {
+                        Key::Ctrl(if shift { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() })
+                    },
+                    _ => return None,
+                }
+            },
+        };
+
+        Some(key)
+    }
+}
+
+/// Writes keys the way vim writes them, like `<Esc>` or `<C-r>`, with `<lt>` for `<`.
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match *self {
+            Key::Char('<') => write!(f, "<lt>"),
+            Key::Char(c) => write!(f, "{c}"),
+            Key::Ctrl(c) if c.is_ascii_uppercase() => write!(f, "<C-S-{}>", c.to_ascii_lowercase()),
+            Key::Ctrl(c) => write!(f, "<C-{c}>"),
+            Key::Esc => write!(f, "<Esc>"),
+            Key::Enter => write!(f, "<CR>"),
+            Key::Backspace => write!(f, "<BS>"),
+            Key::Delete => write!(f, "<Del>"),
+            Key::Tab => write!(f, "<Tab>"),
+            Key::Left => write!(f, "<Left>"),
+            Key::Right => write!(f, "<Right>"),
+            Key::Up => write!(f, "<Up>"),
+            Key::Down => write!(f, "<Down>"),
+        }
+    }
+}
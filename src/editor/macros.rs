@@ -0,0 +1,71 @@
+use super::{Editor, key::Key, register::{Register, RegisterKind}};
+
+/// How deep macros can run other macros, so a macro running itself comes to an end.
+const MAX_DEPTH: usize = 100;
+
+/// A macro being recorded with `q`.
+pub struct Recording {
+    /// The register the macro goes into.
+    register: char,
+    keys: Vec<Key>,
+}
+
+impl Editor {
+    /// Returns the register of the macro being recorded, if any.
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|recording| recording.register)
+    }
+
+    /// `q{register}`, starts recording the keys typed into `register`.
+    pub(super) fn start_recording(&mut self, register: char) {
+        if register.is_ascii_alphanumeric() || register == '"' {
+            self.recording = Some(Recording { register, keys: vec![] });
+        }
+    }
+
+    /// `q` while recording, stores the keys typed since `q{register}` into the register, written
+    /// as text so they can be put and edited like any other text.
+    pub(super) fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            let text: String = recording.keys.iter().map(Key::to_string).collect();
+            self.registers.set_macro(recording.register, Register { text, kind: RegisterKind::Char });
+        }
+    }
+
+    /// Records a key typed while recording a macro. Keys typed by running a macro or by
+    /// `:normal` are not recorded, as the keys that ran them already were.
+    pub(super) fn record_key(&mut self, key: Key) {
+        if let (Some(recording), 0, 0) = (&mut self.recording, self.macro_depth, self.batch) {
+            recording.keys.push(key);
+        }
+    }
+
+    /// `@{register}`, types the keys written in `register` `n` times. `@@` runs the last macro
+    /// run again.
+    pub(super) fn run_macro(&mut self, register: char, n: usize) {
+        let register = if register == '@' { self.last_macro } else { Some(register) };
+        let Some(register) = register else {
+            return;
+        };
+
+        let Some(Register { text, .. }) = self.register(register) else {
+            return;
+        };
+
+        if self.macro_depth >= MAX_DEPTH {
+            return;
+        }
+
+        self.last_macro = Some(register);
+        self.macro_depth += 1;
+
+        let keys = Key::parse(&text);
+        for _ in 0..n {
+            for &key in &keys {
+                self.key(key);
+            }
+        }
+
+        self.macro_depth -= 1;
+    }
+}
@@ -0,0 +1,81 @@
+use std::ops::Range;
+
+use super::Editor;
+
+impl Editor {
+    /// `m{name}`, marks the position of the cursor, which `'{name}` and `` `{name} `` go back to.
+    pub(super) fn set_mark(&mut self, name: char) {
+        if name.is_ascii_lowercase() {
+            self.marks.insert(name, (self.cx, self.cy));
+        }
+    }
+
+    /// Returns the position of the mark `name`, as `(cx, cy)`.
+    pub fn mark(&self, name: char) -> Option<(usize, usize)> {
+        self.marks.get(&name).copied()
+    }
+
+    /// Moves the lines kept by the marks and by `:g` before `text` is inserted at the byte offset
+    /// `pos`, so they stay with the text they were on.
+    pub(super) fn mark_insert(&mut self, pos: usize, text: &str) {
+        let added = text.matches('\n').count();
+        if added == 0 {
+            return;
+        }
+
+        let line = self.buffer.byte_to_line(pos);
+
+        // Whole lines inserted at the start of a line go above it, taking it down with them.
+        let above = pos == self.buffer.line_to_byte(line) && text.ends_with('\n');
+
+        self.adjust_lines(|n| {
+            if n > line || (n == line && above) {
+                Some(n + added)
+            } else {
+                Some(n)
+            }
+        });
+    }
+
+    /// Moves the lines kept by the marks and by `:g` before the text between the byte offsets of
+    /// `range` is removed. The ones on lines going away with the text are dropped.
+    pub(super) fn mark_remove(&mut self, range: &Range<usize>) {
+        let first = self.buffer.byte_to_line(range.start);
+        let last = self.buffer.byte_to_line(range.end);
+
+        let removed = last - first;
+        if removed == 0 {
+            return;
+        }
+
+        // When whole lines go away, the line after them is left as is, otherwise what is left of
+        // it joins the first line.
+        let whole = range.start == self.buffer.line_to_byte(first) && range.end == self.buffer.line_to_byte(last);
+
+        self.adjust_lines(|n| {
+            if n > last || (whole && n == last) {
+                Some(n - removed)
+            } else if n > first || (whole && n == first) {
+                None
+            } else {
+                Some(n)
+            }
+        });
+    }
+
+    fn adjust_lines(&mut self, adjust: impl Fn(usize) -> Option<usize>) {
+        self.marks.retain(|_, (_, cy)| match adjust(*cy) {
+            Some(n) => {
+                *cy = n;
+                true
+            },
+            None => false,
+        });
+
+        if let Some(lines) = &mut self.global_lines {
+            for line in lines.iter_mut() {
+                *line = line.and_then(&adjust);
+            }
+        }
+    }
+}
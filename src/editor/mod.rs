@@ -1,103 +1,246 @@
-use std::{ffi::CString, fmt::{Display, Formatter}};
+use std::{collections::HashMap, fmt::{Display, Formatter}};
 
-use crate::shader::{Program, new_shader};
+use sdl2::clipboard::ClipboardUtil;
 
+use self::{buffer::Buffer, completion::Completion, file::File, history::{Edit, History}, key::Key, macros::Recording, mouse::Drag, movement::Find, normal_mode::Repeat, options::Options, prompt::{Prompt, PromptHistory}, register::Registers, search::{Pattern, Search}, substitute::Substitution, visual_mode::{BlockInsert, Visual, VisualKind}};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Mode {
     Insert,
     Normal,
-    Visual,
+    Visual(Visual),
+    /// Typing a search with `/` or `?`, which started with the cursor at `origin`.
+    Search { forward: bool, origin: (usize, usize) },
+    /// Typing a command after `:`.
+    Command,
+    /// Asking whether to replace each match of a substitution with the `c` flag.
+    Confirm,
 }
 
-
 impl Display for Mode {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         match *self {
             Mode::Insert => write!(f, "Insert"),
             Mode::Normal => write!(f, "Normal"),
-            Mode::Visual => write!(f, "Visual"),
+            Mode::Visual(Visual { kind: VisualKind::Char, .. }) => write!(f, "Visual"),
+            Mode::Visual(Visual { kind: VisualKind::Line, .. }) => write!(f, "Visual Line"),
+            Mode::Visual(Visual { kind: VisualKind::Block, .. }) => write!(f, "Visual Block"),
+            Mode::Search { .. } => write!(f, "Search"),
+            Mode::Command => write!(f, "Command"),
+            Mode::Confirm => write!(f, "Confirm"),
         }
     }
 }
 
 pub struct Editor {
     pub mode: Mode,
-    cmd_stack: String,
+    /// The keys typed so far for the normal mode command being typed.
+    cmd_stack: Vec<Key>,
+    /// The last change, which `.` repeats.
+    last_change: Option<Repeat>,
+    /// The line typed at the bottom of the window, like a search.
+    pub prompt: Prompt,
+    /// The commands and searches typed before, which Up and Down go through.
+    pub prompt_history: PromptHistory,
+    /// The candidates Tab goes through to complete the command being typed.
+    completion: Option<Completion>,
+    /// The last search, which `n` and `N` repeat.
+    last_search: Option<Search>,
+    /// The pattern whose matches are highlighted.
+    highlight: Option<Pattern>,
+    /// The substitution asking whether to replace each match.
+    substitution: Option<Substitution>,
+    /// The edits showing what the command being typed would do, which are not in the history.
+    preview: Vec<Edit>,
+    /// The macro being recorded, if any.
+    recording: Option<Recording>,
+    /// The register of the last macro run, which `@@` runs again.
+    last_macro: Option<char>,
+    /// How many macros are running, one inside the other.
+    macro_depth: usize,
+    /// How many commands typing keys themselves are running, like `:normal`. Everything they do
+    /// is undone at once, so the history is only committed once they are over.
+    batch: usize,
+    /// Whether the editor was asked to quit, like with `:q`.
+    pub quit: bool,
+    /// What the last command had to say, like an error, shown until the next key.
+    pub message: Option<String>,
+    /// The options changed with `:set`.
+    pub options: Options,
+    /// The names of the themes `:colorscheme` can switch to.
+    pub themes: Vec<String>,
+    /// The theme asked for with `:colorscheme`, until the window switches to it.
+    pub colorscheme: Option<String>,
 
     // ===============
     // Text properties
     // ===============
 
-    /// The OpenGL program responsible for rendering the text in the editor.
-    pub t_program: Program,
     /// The content (text/code) of the editor.
-    pub content: String,
-    /// Each element of the vector represents the total number of characters in the corresponding line of the content.
-    lines: Vec<u32>,
+    pub buffer: Buffer,
+    /// The file being edited, if any.
+    pub file: Option<File>,
+    /// Whether the content has changed since it was last read or written.
+    pub dirty: bool,
+    /// Every change made to the content, used to undo and redo them.
+    history: History,
+    /// The text yanked and deleted, which can be put back.
+    registers: Registers,
+    /// The system clipboard, behind the `+` and `*` registers.
+    pub clipboard: Option<ClipboardUtil>,
+    /// The last visual selection and where its cursor was, which `gv` selects again.
+    last_visual: Option<(Visual, (usize, usize))>,
+    /// The block being changed with `c` in visual block mode.
+    block_insert: Option<BlockInsert>,
+    /// The positions marked with `m`, as `(cx, cy)`.
+    marks: HashMap<char, (usize, usize)>,
+    /// The lines `:g` has yet to run its command on, which are `None` once deleted.
+    global_lines: Option<Vec<Option<usize>>>,
 
     // =================
     // Cursor properties
     // =================
-    
-    /// OpenGL program of the cursor.
-    pub c_program: Program,
-    /// Represents the cursor's X position, which is actually the index of the text to modify.
+
+    /// Represents the cursor's X position, which is the index of the grapheme to modify in the
+    /// current line.
     pub cx: usize,
+    /// The column the cursor goes back to when moving vertically through shorter lines.
     cx_keep: usize,
+    /// The last character searched for in a line with `f`, `F`, `t` or `T`, which `;` and `,` repeat.
+    last_find: Option<Find>,
     /// Represents the cursor's Y position, which is the current line of the cursor.
     pub cy: usize,
+
+    // ===================
+    // Viewport properties
+    // ===================
+
+    /// The first line on the screen.
+    pub top: usize,
+    /// The first column on the screen.
+    pub left: usize,
+    /// How many lines and columns of text fit on the screen.
+    rows: usize,
+    columns: usize,
+    /// How far `Ctrl-D` and `Ctrl-U` scroll, which is half the screen when `0`.
+    scroll_lines: usize,
+    /// The drag of the mouse selecting text, while its button is held down.
+    drag: Option<Drag>,
+    /// Whether the screen was scrolled with the mouse since the last key, which may have left the
+    /// cursor out of it.
+    pub scrolled: bool,
 }
 
 impl Editor {
-    pub fn new(app_w: f32, app_h: f32, data: &str) -> Result<Self, String> {
-        let t_program = Program::new(
-            &[
-                new_shader(&CString::new(include_str!("../shader/char.v.glsl")).unwrap(), gl::VERTEX_SHADER).unwrap(),
-                new_shader(&CString::new(include_str!("../shader/char.f.glsl")).unwrap(), gl::FRAGMENT_SHADER).unwrap(),
-            ],
-            app_w, 
-            app_h,
-        )?;
-
-        let c_program = Program::new(
-            &[
-                new_shader(&CString::new(include_str!("../shader/cursor.v.glsl")).unwrap(), gl::VERTEX_SHADER)?,
-                new_shader(&CString::new(include_str!("../shader/cursor.f.glsl")).unwrap(), gl::FRAGMENT_SHADER)?,
-            ],
-            app_w,
-            app_h,
-        )?;
-
-        Ok(Self {
+    /// Creates an editor with `data` as its content. Rendering it is up to the window, so the
+    /// editor needs no OpenGL context.
+    pub fn new(data: &str) -> Self {
+        Self {
             mode: Mode::Normal,
-            cmd_stack: "".to_string(),
+            cmd_stack: vec![],
+            last_change: None,
+            prompt: Prompt::default(),
+            prompt_history: PromptHistory::default(),
+            completion: None,
+            last_search: None,
+            highlight: None,
+            substitution: None,
+            preview: vec![],
+            recording: None,
+            last_macro: None,
+            macro_depth: 0,
+            batch: 0,
+            quit: false,
+            message: None,
+            options: Options::default(),
+            themes: vec![],
+            colorscheme: None,
 
-            t_program,
-            content: data.to_string(),
-            lines: vec![0],
-
-            c_program,
+            buffer: Buffer::new(data),
+            file: None,
+            dirty: false,
+            history: History::default(),
+            registers: Registers::default(),
+            clipboard: None,
+            last_visual: None,
+            block_insert: None,
+            marks: HashMap::new(),
+            global_lines: None,
 
             cx: 0,
             cx_keep: 0,
+            last_find: None,
 
             cy: 0,
-        })
+
+            top: 0,
+            left: 0,
+            rows: 0,
+            columns: 0,
+            scroll_lines: 0,
+            drag: None,
+            scrolled: false,
+        }
     }
 }
 
 impl Editor {
     pub fn event(&mut self, event: sdl2::event::Event) {
+        for key in Key::from_event(&event) {
+            self.key(key);
+        }
+    }
+
+    /// Handles a key pressed by the user according to the current mode.
+    pub fn key(&mut self, key: Key) {
+        if self.batch == 0 {
+            self.message = None;
+        }
+
+        // The cursor is only brought back into the screen scrolled with the mouse once a key is
+        // typed, so scrolling does not move it.
+        if self.scrolled {
+            self.scrolled = false;
+            self.cursor_into_view();
+        }
+
+        // "q" stops recording a macro, unless it is part of a command being typed.
+        let stop = matches!(self.mode, Mode::Normal | Mode::Visual(_)) && self.cmd_stack.is_empty();
+        if key == Key::Char('q') && stop && self.recording.is_some() {
+            self.stop_recording();
+            return;
+        }
+
+        self.record_key(key);
+
         match self.mode {
             Mode::Insert => {
-                self.insert_mode(event)
+                self.insert_mode(key)
             },
             Mode::Normal => {
-                self.normal_mode(event)
+                self.normal_mode(key)
+            },
+            Mode::Visual(_) => {
+                self.visual_mode(key)
+            },
+            Mode::Search { .. } => {
+                self.search_mode(key)
             },
-            Mode::Visual => {
-                self.visual_mode(event)
+            Mode::Command => {
+                self.command_mode(key)
             },
+            Mode::Confirm => {
+                self.confirm_mode(key)
+            },
+        }
+
+        // Everything typed during an insert session, or while confirming the matches of a
+        // substitution, is undone at once, so the change is only closed once it is over.
+        if self.batch == 0 && !matches!(self.mode, Mode::Insert | Mode::Confirm) {
+            self.history.commit();
         }
+
+        self.scroll_to_cursor();
     }
 }
 
@@ -138,40 +281,90 @@ impl Editor {
     ///
     /// ### Example
     /// ```
-    /// self.lines = vec![5];
+    /// self.buffer = Buffer::new("hello");
     /// self.cy = 0;
     ///
     /// assert_eq!(get_line_width(0), 5);
     /// ```
     #[inline]
     fn get_line_width(&self, n: i32) -> usize {
-        self.lines[self.get_line(n)] as usize
+        self.buffer.line_width(self.get_line(n))
     }
 
-    #[inline]
-    fn line(&self) -> usize {
-        if self.lines.len() == 1 {
-            return 0;
-        }
-        
-        self.lines[0..self.cy]
-            .iter()
-            .fold(0, |acc, line| acc + 1 + *line as usize)
-    }
-    
     /// Determines the absolute cursor position considering both its line and column positions.
+    /// Since `cx` counts graphemes, the returned value is the byte offset where the grapheme under
+    /// the cursor starts.
     #[inline]
     fn cursor_pos(&self) -> usize {
-        self.line() + self.cx
+        self.buffer.grapheme_to_byte(self.cy, self.cx)
     }
 
     #[inline]
     fn content_lines(&self) -> usize {
-        self.lines.len() - 1
+        self.buffer.len_lines() - 1
+    }
+
+    /// Inserts `text` at the byte offset `pos`. Every change to the buffer goes through here or
+    /// [`Editor::buffer_remove`], so the editor can keep track of them.
+    fn buffer_insert(&mut self, pos: usize, text: &str) {
+        self.mark_insert(pos, text);
+        self.buffer.insert(pos, text);
+        self.dirty = true;
+
+        let edit = Edit::Insert { pos, text: text.to_string() };
+        self.history.record(edit, (self.cx, self.cy));
+    }
+
+    /// Removes the text between the byte offsets of `range` and returns it.
+    fn buffer_remove(&mut self, range: std::ops::Range<usize>) -> String {
+        let pos = range.start;
+        self.mark_remove(&range);
+        let text = self.buffer.remove(range);
+        self.dirty = true;
+
+        let edit = Edit::Remove { pos, text: text.clone() };
+        self.history.record(edit, (self.cx, self.cy));
+
+        text
+    }
+
+    /// Replaces the text between the byte offsets of `range` with `text`.
+    fn buffer_replace(&mut self, range: std::ops::Range<usize>, text: &str) {
+        let start = range.start;
+
+        if !range.is_empty() {
+            self.buffer_remove(range);
+        }
+
+        if !text.is_empty() {
+            self.buffer_insert(start, text);
+        }
     }
 }
 
+pub mod buffer;
+pub mod command;
+pub mod command_mode;
+pub mod completion;
+pub mod ex;
+pub mod file;
+pub mod filter;
+pub mod history;
+pub mod key;
+pub mod macros;
+pub mod marks;
+pub mod mouse;
 pub mod movement;
 pub mod insert_mode;
 pub mod normal_mode;
+pub mod operator;
+pub mod options;
+pub mod prompt;
+pub mod register;
+pub mod search;
+pub mod search_mode;
+pub mod substitute;
+pub mod textobject;
+pub mod viewport;
 pub mod visual_mode;
+pub mod wrap;
@@ -0,0 +1,110 @@
+use super::{Editor, Mode, textobject::{ObjectKind, TextObject}, visual_mode::{Visual, VisualKind}};
+
+/// A drag of the mouse with its left button held down, which selects the text it goes over.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Drag {
+    /// Where the button was pressed, as `(cx, cy)`.
+    start: (usize, usize),
+    /// Whether it selects more than the text under the cursor, like after a double click.
+    selecting: bool,
+}
+
+impl Editor {
+    /// Whether the mouse can move the cursor, which it cannot while typing at the bottom of the
+    /// window.
+    fn mouse_enabled(&self) -> bool {
+        matches!(self.mode, Mode::Normal | Mode::Insert | Mode::Visual(_))
+    }
+
+    /// Handles `clicks` clicks of the left button on the cell at `row` and `column` of the screen:
+    /// a click moves the cursor there, a double click selects the word there and a triple click
+    /// selects its line.
+    pub fn click(&mut self, row: usize, column: usize, clicks: u8) {
+        if !self.mouse_enabled() {
+            return;
+        }
+
+        self.scrolled = false;
+        self.exit_visual();
+
+        (self.cx, self.cy) = self.screen_position(row, column);
+        self.cx_keep = self.cx;
+
+        // Selecting with the mouse in insert mode is left to normal mode
+        if matches!(self.mode, Mode::Insert) {
+            self.history.commit();
+            self.drag = None;
+            return;
+        }
+
+        match clicks {
+            1 => { },
+            2 => self.select_object(TextObject { kind: ObjectKind::Word { big: false }, around: false }, None),
+            _ => self.set_mode(Mode::Visual(Visual { kind: VisualKind::Line, anchor: (self.cx, self.cy) })),
+        }
+
+        self.drag = Some(Drag { start: (self.cx, self.cy), selecting: clicks > 1 });
+    }
+
+    /// Moves the end of the selection to the cell at `row` and `column` of the screen while
+    /// dragging the mouse, starting a selection once it leaves the cell it was pressed on.
+    pub fn drag(&mut self, row: usize, column: usize) {
+        let Some(drag) = self.drag else {
+            return;
+        };
+
+        let (cx, cy) = self.screen_position(row, column);
+
+        if !matches!(self.mode, Mode::Visual(_)) {
+            if (cx, cy) == drag.start || drag.selecting {
+                return;
+            }
+
+            self.set_mode(Mode::Visual(Visual { kind: VisualKind::Char, anchor: drag.start }));
+        }
+
+        (self.cx, self.cy) = (cx, cy);
+        self.cx_keep = cx;
+        self.scroll_to_cursor();
+    }
+
+    /// Ends a drag of the mouse, making the text it selected the primary selection.
+    pub fn release(&mut self) {
+        if self.drag.take().is_none() {
+            return;
+        }
+
+        if let Some((range, _)) = self.selection_range(false) {
+            let text = self.buffer.slice(range).to_string();
+            self.set_clipboard('*', &text);
+        }
+    }
+
+    /// Puts the text of the primary selection at the cell at `row` and `column` of the screen,
+    /// as one change.
+    pub fn middle_click(&mut self, row: usize, column: usize) {
+        if !self.mouse_enabled() {
+            return;
+        }
+
+        self.scrolled = false;
+        self.exit_visual();
+        (self.cx, self.cy) = self.screen_position(row, column);
+
+        match self.mode {
+            Mode::Insert => {
+                let Some(register) = self.register('*') else {
+                    return;
+                };
+
+                self.paste(&register.text);
+            },
+            _ => {
+                self.put(Some('*'), true, false, 1);
+                self.history.commit();
+            },
+        }
+
+        self.cx_keep = self.cx;
+    }
+}
@@ -1,6 +1,10 @@
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::util::usize_sub;
 
-use super::Editor;
+use super::{Editor, search::Pattern};
 
 impl Editor {
     /// if n = 0, move to the beginning of the file.
@@ -50,7 +54,7 @@ impl Editor {
         let line_width = self.get_line_width(0);
         let new_cx = self.cx + n;
 
-        if (n != 1 && new_cx >= line_width) || (new_cx > line_width && self.cy == self.lines.len() - 1) {
+        if (n != 1 && new_cx >= line_width) || (new_cx > line_width && self.cy == self.content_lines()) {
             self.cx = line_width;
         } else if new_cx > line_width {
             self.cx = 0;
@@ -80,3 +84,778 @@ impl Editor {
     }
 }
 
+
+/// A motion that moves the cursor, which operators also use to know what text to act on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Motion {
+    /// `h`
+    Left,
+    /// `l`
+    Right,
+    /// `k`
+    Up,
+    /// `j`
+    Down,
+    /// `0`
+    LineStart,
+    /// `^`
+    FirstNonBlank,
+    /// `$`
+    LineEnd,
+    /// `|`, to the column given by the count.
+    Column,
+    /// `+`, to the first non-blank character of the next line.
+    NextLine,
+    /// `-`, to the first non-blank character of the previous line.
+    PrevLine,
+    /// `gg`, to the line given by the count or the first one.
+    FirstLine,
+    /// `G`, to the line given by the count or the last one.
+    LastLine,
+    /// `w` and `W`, to the start of the next word.
+    NextWord { big: bool },
+    /// `b` and `B`, to the start of the previous word.
+    PrevWord { big: bool },
+    /// `e` and `E`, to the end of the next word.
+    NextWordEnd { big: bool },
+    /// `ge` and `gE`, to the end of the previous word.
+    PrevWordEnd { big: bool },
+    /// `)`, to the start of the next sentence.
+    NextSentence,
+    /// `(`, to the start of the previous sentence.
+    PrevSentence,
+    /// `}`, to the empty line after the paragraph.
+    NextParagraph,
+    /// `{`, to the empty line before the paragraph.
+    PrevParagraph,
+    /// `f`, `F`, `t` and `T`, to a character in the line. When `again` is set, the find is being
+    /// repeated, so a till stopping right before the character would jump over it instead.
+    Find { find: Find, again: bool },
+    /// `;` and `,`, repeat the last find, going the other way when `reverse` is set.
+    RepeatFind { reverse: bool },
+    /// `n` and `N`, to the next match of the last search, going the other way when `reverse` is
+    /// set.
+    SearchNext { reverse: bool },
+    /// `*` and `#`, to the next match of the word under the cursor.
+    SearchWord { forward: bool },
+    /// `'` and `` ` ``, to the line of a mark, or to its exact position when `exact` is set.
+    Mark { name: char, exact: bool },
+    /// `H`, to the line given by the count from the top of the screen.
+    ScreenTop,
+    /// `M`, to the line in the middle of the screen.
+    ScreenMiddle,
+    /// `L`, to the line given by the count from the bottom of the screen.
+    ScreenBottom,
+    /// `gj`, down a row of the screen, which is a part of a line with 'wrap'.
+    ScreenDown,
+    /// `gk`, up a row of the screen.
+    ScreenUp,
+    /// `g0`, to the first character of the row of the screen.
+    ScreenLineStart,
+    /// `g$`, to the last character of the row of the screen.
+    ScreenLineEnd,
+}
+
+/// A search for a character in the line of the cursor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Find {
+    pub c: char,
+    pub forward: bool,
+    /// Whether the cursor stops right before the character instead of over it, like `t` does.
+    pub till: bool,
+}
+
+/// How an operator treats the text between the cursor and the target of a motion.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MotionKind {
+    /// The character at the end is left out.
+    Exclusive,
+    /// The character at the end is included.
+    Inclusive,
+    /// Every line touched is included as a whole.
+    Linewise,
+}
+
+impl Motion {
+    pub fn kind(&self) -> MotionKind {
+        match self {
+            Motion::Left
+            | Motion::Right
+            | Motion::LineStart
+            | Motion::FirstNonBlank
+            | Motion::Column
+            | Motion::NextWord { .. }
+            | Motion::PrevWord { .. }
+            | Motion::NextSentence
+            | Motion::PrevSentence
+            | Motion::NextParagraph
+            | Motion::PrevParagraph
+            | Motion::RepeatFind { .. }
+            | Motion::SearchNext { .. }
+            | Motion::SearchWord { .. }
+            | Motion::Mark { exact: true, .. }
+            | Motion::ScreenDown
+            | Motion::ScreenUp
+            | Motion::ScreenLineStart => MotionKind::Exclusive,
+            Motion::Find { find, .. } if !find.forward => MotionKind::Exclusive,
+            Motion::LineEnd
+            | Motion::NextWordEnd { .. }
+            | Motion::PrevWordEnd { .. }
+            | Motion::Find { .. }
+            | Motion::ScreenLineEnd => MotionKind::Inclusive,
+            Motion::Up
+            | Motion::Down
+            | Motion::NextLine
+            | Motion::PrevLine
+            | Motion::FirstLine
+            | Motion::LastLine
+            | Motion::Mark { .. }
+            | Motion::ScreenTop
+            | Motion::ScreenMiddle
+            | Motion::ScreenBottom => MotionKind::Linewise,
+        }
+    }
+
+    /// Whether the motion moves between lines keeping the column, which is how `j` and `k` can
+    /// go through short lines and come back to the column they started from.
+    #[inline]
+    pub fn is_vertical(&self) -> bool {
+        matches!(self, Motion::Up | Motion::Down)
+    }
+}
+
+impl Editor {
+    /// Returns where `motion` moves the cursor to, as `(cx, cy)`, repeating it `count` times.
+    /// Returns `None` when the motion cannot move the cursor at all.
+    pub fn motion_target(&self, motion: Motion, count: Option<usize>) -> Option<(usize, usize)> {
+        let n = count.unwrap_or(1);
+        let last_line = self.content_lines();
+
+        let target = match motion {
+            Motion::Left => {
+                if self.cx == 0 {
+                    return None;
+                }
+
+                (usize_sub(self.cx, n), self.cy)
+            },
+            Motion::Right => {
+                let cx = (self.cx + n).min(self.get_line_width(0));
+                if cx <= self.cx {
+                    return None;
+                }
+
+                (cx, self.cy)
+            },
+            Motion::Up => {
+                if self.cy == 0 {
+                    return None;
+                }
+
+                let cy = usize_sub(self.cy, n);
+                (self.cx_keep.min(self.buffer.line_width(cy)), cy)
+            },
+            Motion::Down => {
+                if self.cy == last_line {
+                    return None;
+                }
+
+                let cy = (self.cy + n).min(last_line);
+                (self.cx_keep.min(self.buffer.line_width(cy)), cy)
+            },
+            Motion::LineStart => (0, self.cy),
+            Motion::FirstNonBlank => (self.first_non_blank(self.cy), self.cy),
+            Motion::LineEnd => {
+                let cy = (self.cy + n - 1).min(last_line);
+                (usize_sub(self.buffer.line_width(cy), 1), cy)
+            },
+            Motion::Column => (usize_sub(n, 1).min(self.get_line_width(0)), self.cy),
+            Motion::NextLine => {
+                if self.cy == last_line {
+                    return None;
+                }
+
+                let cy = (self.cy + n).min(last_line);
+                (self.first_non_blank(cy), cy)
+            },
+            Motion::PrevLine => {
+                if self.cy == 0 {
+                    return None;
+                }
+
+                let cy = usize_sub(self.cy, n);
+                (self.first_non_blank(cy), cy)
+            },
+            Motion::FirstLine | Motion::LastLine => {
+                let cy = match count {
+                    Some(n) => usize_sub(n, 1).min(last_line),
+                    None if motion == Motion::FirstLine => 0,
+                    None => last_line,
+                };
+
+                (self.first_non_blank(cy), cy)
+            },
+            Motion::NextWord { big } => self.repeat(n, big, Scanner::next_word)?,
+            Motion::PrevWord { big } => self.repeat(n, big, Scanner::prev_word)?,
+            Motion::NextWordEnd { big } => self.repeat(n, big, Scanner::next_word_end)?,
+            Motion::PrevWordEnd { big } => self.repeat(n, big, Scanner::prev_word_end)?,
+            Motion::NextSentence => self.repeat(n, false, Scanner::next_sentence)?,
+            Motion::PrevSentence => self.repeat(n, false, Scanner::prev_sentence)?,
+            Motion::NextParagraph => self.repeat(n, false, Scanner::next_paragraph)?,
+            Motion::PrevParagraph => self.repeat(n, false, Scanner::prev_paragraph)?,
+            Motion::Find { find, again } => (self.find_in_line(find, n, again)?, self.cy),
+            Motion::SearchNext { reverse } => {
+                let search = self.last_search.as_ref()?;
+                let pattern = Pattern::new(&search.pattern)?;
+
+                self.search(&pattern, (self.cx, self.cy), search.forward != reverse, n)?
+            },
+            Motion::Mark { name, exact } => {
+                let (cx, cy) = self.mark(name)?;
+                let cy = cy.min(last_line);
+
+                if exact {
+                    (cx.min(self.buffer.line_width(cy)), cy)
+                } else {
+                    (self.first_non_blank(cy), cy)
+                }
+            },
+            Motion::ScreenTop | Motion::ScreenMiddle | Motion::ScreenBottom => {
+                let cy = self.screen_line(motion, n);
+                (self.first_non_blank(cy), cy)
+            },
+            Motion::ScreenDown | Motion::ScreenUp | Motion::ScreenLineStart | Motion::ScreenLineEnd => {
+                self.screen_motion_target(motion, n)?
+            },
+            Motion::RepeatFind { .. } | Motion::SearchWord { .. } => return None,
+        };
+
+        Some(target)
+    }
+
+    /// Turns `;` and `,` into the find they repeat, and remembers any other find so it can be
+    /// repeated later. Returns `None` when there is no find to repeat yet.
+    pub fn resolve_find(&mut self, motion: Motion) -> Option<Motion> {
+        match motion {
+            Motion::RepeatFind { reverse } => {
+                let mut find = self.last_find?;
+                find.forward ^= reverse;
+
+                Some(Motion::Find { find, again: true })
+            },
+            Motion::Find { find, .. } => {
+                self.last_find = Some(find);
+                Some(motion)
+            },
+            _ => Some(motion),
+        }
+    }
+
+    /// Returns the column `find` moves the cursor to, looking for the `n`th occurrence of its
+    /// character in the line. Returns `None` when there are not enough occurrences.
+    fn find_in_line(&self, find: Find, n: usize, again: bool) -> Option<usize> {
+        let line: Vec<char> = self.buffer
+            .line(self.cy)
+            .graphemes(true)
+            .map(|g| g.chars().next().unwrap_or(' '))
+            .collect();
+
+        // A till that is repeated would find the same character again without moving.
+        let skip = usize::from(find.till && again);
+
+        let col = if find.forward {
+            line.iter()
+                .enumerate()
+                .skip(self.cx + 1 + skip)
+                .filter(|(_, c)| **c == find.c)
+                .nth(n - 1)?
+                .0
+        } else {
+            line.iter()
+                .enumerate()
+                .take(usize_sub(self.cx, skip))
+                .rev()
+                .filter(|(_, c)| **c == find.c)
+                .nth(n - 1)?
+                .0
+        };
+
+        match (find.till, find.forward) {
+            (false, _) => Some(col),
+            (true, true) => Some(col - 1),
+            (true, false) => Some(col + 1),
+        }
+    }
+
+    /// Moves a scanner from the cursor with `step` `n` times and returns where it ends up.
+    /// Returns `None` only when the first step cannot move at all.
+    fn repeat<'a>(&'a self, n: usize, big: bool, step: fn(&mut Scanner<'a>) -> bool) -> Option<(usize, usize)> {
+        let mut scanner = Scanner::new(self, self.cx, self.cy, big);
+
+        for i in 0..n {
+            if !step(&mut scanner) {
+                if i == 0 {
+                    return None;
+                }
+
+                break;
+            }
+        }
+
+        Some(scanner.pos())
+    }
+
+    /// Returns where the `n`th word counting from the one under the cursor ends, as `(cx, cy)`.
+    pub fn current_word_end(&self, n: usize, big: bool) -> (usize, usize) {
+        let mut scanner = Scanner::new(self, self.cx, self.cy, big);
+        scanner.word_end();
+
+        for _ in 1..n {
+            if !scanner.next_word_end() {
+                break;
+            }
+        }
+
+        scanner.pos()
+    }
+
+    /// Returns the column of the first character of the line `n` that is not a blank.
+    pub fn first_non_blank(&self, n: usize) -> usize {
+        self.buffer
+            .line(n)
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .count()
+    }
+}
+
+/// The class of a character, which tells where words start and end.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CharClass {
+    /// Spaces, tabs and the end of a line.
+    Blank,
+    /// Every other character, like `.` or `(`.
+    Punctuation,
+    /// Letters, digits and `_`.
+    Keyword,
+}
+
+impl CharClass {
+    /// Returns the class of `c`. When `big` is set, words are WORDs, in which only blanks matter.
+    pub fn of(c: char, big: bool) -> Self {
+        if c.is_whitespace() {
+            CharClass::Blank
+        } else if big || c.is_alphanumeric() || c == '_' {
+            CharClass::Keyword
+        } else {
+            CharClass::Punctuation
+        }
+    }
+}
+
+/// A position in the buffer that steps through every grapheme, going through the end of each
+/// line as if it were a blank character.
+///
+/// The graphemes of the current line are kept as their first character, since that is all
+/// motions need to classify them.
+pub struct Scanner<'a> {
+    editor: &'a Editor,
+    /// Whether words are WORDs.
+    big: bool,
+    line: Vec<char>,
+    cx: usize,
+    cy: usize,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(editor: &'a Editor, cx: usize, cy: usize, big: bool) -> Self {
+        let mut scanner = Self {
+            editor,
+            big,
+            line: vec![],
+            cx,
+            cy,
+        };
+
+        scanner.load(cy);
+        scanner.cx = cx.min(scanner.line.len());
+
+        scanner
+    }
+
+    fn load(&mut self, cy: usize) {
+        self.cy = cy;
+        self.line = self.line_chars(cy);
+    }
+
+    /// Returns the characters of the line `cy`, one for each grapheme.
+    fn line_chars(&self, cy: usize) -> Vec<char> {
+        self.editor.buffer
+            .line(cy)
+            .graphemes(true)
+            .map(|g| g.chars().next().unwrap_or(' '))
+            .collect()
+    }
+
+    /// Returns the position of the scanner, as `(cx, cy)`.
+    #[inline]
+    pub fn pos(&self) -> (usize, usize) {
+        (self.cx, self.cy)
+    }
+
+    /// Returns the character at the scanner, or `None` at the end of a line.
+    #[inline]
+    pub fn char(&self) -> Option<char> {
+        self.line.get(self.cx).copied()
+    }
+
+    #[inline]
+    pub fn class(&self) -> CharClass {
+        self.char().map_or(CharClass::Blank, |c| CharClass::of(c, self.big))
+    }
+
+    /// Whether the scanner is on an empty line, which motions treat as a word of its own.
+    #[inline]
+    pub fn at_empty_line(&self) -> bool {
+        self.line.is_empty()
+    }
+
+    /// Moves to the next position, returning `false` at the end of the buffer.
+    pub fn next(&mut self) -> bool {
+        if self.cx < self.line.len() {
+            self.cx += 1;
+        } else if self.cy < self.editor.content_lines() {
+            self.load(self.cy + 1);
+            self.cx = 0;
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    /// Moves to the previous position, returning `false` at the start of the buffer.
+    pub fn prev(&mut self) -> bool {
+        if self.cx > 0 {
+            self.cx -= 1;
+        } else if self.cy > 0 {
+            self.load(self.cy - 1);
+            self.cx = self.line.len();
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    /// Moves to the start of the next word. At the last word of the buffer, it moves to the end
+    /// of the buffer instead.
+    pub fn next_word(&mut self) -> bool {
+        let start = self.pos();
+        let class = self.class();
+
+        if class != CharClass::Blank {
+            while self.class() == class {
+                if !self.next() {
+                    return self.pos() != start;
+                }
+            }
+        }
+
+        while self.class() == CharClass::Blank {
+            if self.at_empty_line() && self.pos() != start {
+                break;
+            }
+
+            if !self.next() {
+                break;
+            }
+        }
+
+        self.pos() != start
+    }
+
+    /// Moves to the start of the previous word.
+    pub fn prev_word(&mut self) -> bool {
+        if !self.prev() {
+            return false;
+        }
+
+        while self.class() == CharClass::Blank && !self.at_empty_line() {
+            if !self.prev() {
+                return true;
+            }
+        }
+
+        self.word_start();
+        true
+    }
+
+    /// Moves to the end of the next word.
+    pub fn next_word_end(&mut self) -> bool {
+        let start = self.pos();
+        if !self.next() {
+            return false;
+        }
+
+        while self.class() == CharClass::Blank {
+            if !self.next() {
+                // There is no word ahead, so stay where the scanner was.
+                *self = Self::new(self.editor, start.0, start.1, self.big);
+                return false;
+            }
+        }
+
+        self.word_end();
+        true
+    }
+
+    /// Moves to the end of the previous word.
+    pub fn prev_word_end(&mut self) -> bool {
+        let start = self.pos();
+        let class = self.class();
+
+        if class != CharClass::Blank {
+            while self.class() == class {
+                if !self.prev() {
+                    return self.pos() != start;
+                }
+            }
+        }
+
+        while self.class() == CharClass::Blank {
+            if self.at_empty_line() && self.pos() != start {
+                break;
+            }
+
+            if !self.prev() {
+                break;
+            }
+        }
+
+        self.pos() != start
+    }
+
+    /// Moves back to the first character of the word the scanner is in.
+    pub fn word_start(&mut self) {
+        let class = self.class();
+        while self.cx > 0 && CharClass::of(self.line[self.cx - 1], self.big) == class {
+            self.cx -= 1;
+        }
+    }
+
+    /// Moves forward to the last character of the word the scanner is in.
+    pub fn word_end(&mut self) {
+        let class = self.class();
+        while self.cx + 1 < self.line.len() && CharClass::of(self.line[self.cx + 1], self.big) == class {
+            self.cx += 1;
+        }
+    }
+
+    /// Moves to the start of the next sentence, or the next empty line after a paragraph.
+    pub fn next_sentence(&mut self) -> bool {
+        let start = self.pos();
+
+        while self.next() {
+            if self.at_sentence_start() {
+                return true;
+            }
+        }
+
+        // The end of the buffer ends the last sentence.
+        self.pos() != start
+    }
+
+    /// Moves to the start of the sentence the scanner is in, or of the previous one when it is
+    /// already at the start.
+    pub fn prev_sentence(&mut self) -> bool {
+        let start = self.pos();
+
+        while self.prev() {
+            if self.at_sentence_start() {
+                return true;
+            }
+        }
+
+        self.pos() != start
+    }
+
+    /// Whether the scanner is at the start of a sentence.
+    ///
+    /// A sentence starts at the first non-blank character after a `.`, `!` or `?` that is
+    /// followed by a blank or the end of the line, optionally with closing `)`, `]`, `"` or `'`
+    /// characters in between. Empty lines also separate sentences, so the first character after
+    /// them starts a sentence, and the first empty line after a paragraph is one on its own.
+    pub fn at_sentence_start(&self) -> bool {
+        if self.at_empty_line() {
+            return self.cy == 0 || !self.editor.buffer.line(self.cy - 1).is_empty();
+        }
+
+        if self.class() == CharClass::Blank {
+            return false;
+        }
+
+        // The characters before the scanner, going up through the lines above only while there
+        // are only blanks before it, so the line is not read again at every step.
+        let mut before = Cow::Borrowed(&self.line[..self.cx]);
+        let mut cy = self.cy;
+        let mut blanks = false;
+
+        let end = loop {
+            if let Some(end) = before.iter().rposition(|c| !matches!(c, ' ' | '\t')) {
+                blanks |= end + 1 < before.len();
+                break end;
+            }
+
+            // The first character of the buffer.
+            if cy == 0 {
+                return true;
+            }
+
+            // The line break is a blank too.
+            cy -= 1;
+            blanks = true;
+            before = Cow::Owned(self.line_chars(cy));
+
+            if before.is_empty() {
+                return true;
+            }
+        };
+
+        if !blanks {
+            return false;
+        }
+
+        let closing = before[..=end].iter().rev().take_while(|c| matches!(c, ')' | ']' | '"' | '\'')).count();
+        closing <= end && matches!(before[end - closing], '.' | '!' | '?')
+    }
+
+    /// Moves to the next empty line after a non-empty one, or to the end of the buffer.
+    pub fn next_paragraph(&mut self) -> bool {
+        let start = self.pos();
+        let last_line = self.editor.content_lines();
+
+        let mut cy = self.cy;
+        // Skip the empty lines the scanner is in.
+        while cy < last_line && self.editor.buffer.line_width(cy) == 0 {
+            cy += 1;
+        }
+
+        while cy < last_line && self.editor.buffer.line_width(cy) != 0 {
+            cy += 1;
+        }
+
+        self.load(cy);
+        self.cx = if self.at_empty_line() { 0 } else { self.line.len() };
+
+        self.pos() != start
+    }
+
+    /// Moves to the previous empty line before a non-empty one, or to the start of the buffer.
+    pub fn prev_paragraph(&mut self) -> bool {
+        let start = self.pos();
+
+        let mut cy = self.cy;
+        while cy > 0 && self.editor.buffer.line_width(cy) == 0 {
+            cy -= 1;
+        }
+
+        while cy > 0 && self.editor.buffer.line_width(cy) != 0 {
+            cy -= 1;
+        }
+
+        self.load(cy);
+        self.cx = 0;
+
+        self.pos() != start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::key::Key;
+    use super::*;
+
+    /// Returns where typing `keys` in normal mode moves the cursor from `start`, as `(cx, cy)`.
+    fn moved(text: &str, start: (usize, usize), keys: &str) -> (usize, usize) {
+        let mut editor = Editor::new(text);
+        (editor.cx, editor.cy) = start;
+
+        for key in Key::parse(keys) {
+            editor.key(key);
+        }
+
+        (editor.cx, editor.cy)
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(moved("foo bar\nbaz", (0, 0), "w"), (4, 0));
+        assert_eq!(moved("foo bar\nbaz", (0, 0), "2w"), (0, 1));
+        assert_eq!(moved("foo.bar baz", (0, 0), "w"), (3, 0));
+        assert_eq!(moved("foo.bar baz", (0, 0), "W"), (8, 0));
+        assert_eq!(moved("foo\n\nbar", (0, 0), "w"), (0, 1));
+
+        assert_eq!(moved("foo bar", (6, 0), "b"), (4, 0));
+        assert_eq!(moved("foo\n  bar", (2, 1), "b"), (0, 0));
+        assert_eq!(moved("foo.bar baz", (8, 0), "B"), (0, 0));
+
+        assert_eq!(moved("foo bar", (0, 0), "e"), (2, 0));
+        assert_eq!(moved("foo bar", (2, 0), "e"), (6, 0));
+        assert_eq!(moved("a.b c", (0, 0), "E"), (2, 0));
+        assert_eq!(moved("foo\n\nbar", (2, 0), "e"), (2, 2));
+
+        assert_eq!(moved("foo bar", (5, 0), "ge"), (2, 0));
+        assert_eq!(moved("foo\nbar", (1, 1), "ge"), (2, 0));
+        assert_eq!(moved("a.b c", (4, 0), "gE"), (2, 0));
+    }
+
+    #[test]
+    fn words_over_graphemes() {
+        // "e" followed by a combining acute accent is a single grapheme
+        assert_eq!(moved("ae\u{301}b cd", (0, 0), "w"), (4, 0));
+        assert_eq!(moved("ae\u{301}b cd", (0, 0), "e"), (2, 0));
+        assert_eq!(moved("ae\u{301}b cd", (4, 0), "b"), (0, 0));
+        assert_eq!(moved("👩‍👩‍👧 x", (0, 0), "W"), (2, 0));
+    }
+
+    #[test]
+    fn sentences() {
+        assert_eq!(moved("One. Two three. Four", (0, 0), ")"), (5, 0));
+        assert_eq!(moved("One. Two three. Four", (0, 0), "2)"), (16, 0));
+        assert_eq!(moved("One. Two three. Four", (16, 0), "("), (5, 0));
+        assert_eq!(moved("One. Two three. Four", (10, 0), "("), (5, 0));
+        assert_eq!(moved("One.\nTwo.", (0, 0), ")"), (0, 1));
+        assert_eq!(moved("One\n\nTwo", (0, 0), ")"), (0, 1));
+        assert_eq!(moved("One\n\nTwo", (0, 1), ")"), (0, 2));
+        assert_eq!(moved("(One.) Two", (0, 0), ")"), (7, 0));
+    }
+
+    #[test]
+    fn paragraphs() {
+        assert_eq!(moved("a\nb\n\nc\nd", (0, 0), "}"), (0, 2));
+        assert_eq!(moved("a\nb\n\nc\nd", (0, 2), "}"), (0, 4));
+        assert_eq!(moved("a\nb\n\nc\nd", (0, 4), "{"), (0, 2));
+        assert_eq!(moved("a\nb\n\n\nc", (0, 4), "{"), (0, 3));
+        assert_eq!(moved("a\nb\n\nc\nd", (0, 1), "{"), (0, 0));
+    }
+
+    #[test]
+    fn finds() {
+        assert_eq!(moved("a,b,c,d", (0, 0), "f,"), (1, 0));
+        assert_eq!(moved("a,b,c,d", (0, 0), "2f,"), (3, 0));
+        assert_eq!(moved("a,b,c,d", (0, 0), "f,;"), (3, 0));
+        assert_eq!(moved("a,b,c,d", (0, 0), "f,;,"), (1, 0));
+        assert_eq!(moved("a,b,c,d", (6, 0), "F,"), (5, 0));
+        assert_eq!(moved("abc,def,g", (0, 0), "t,"), (2, 0));
+        assert_eq!(moved("abc,def,g", (8, 0), "T,"), (8, 0));
+        assert_eq!(moved("abc,def,g", (0, 0), "t,;"), (6, 0));
+        assert_eq!(moved("abc,def,g", (6, 0), "T,"), (4, 0));
+
+        // Finds stay in the line of the cursor
+        assert_eq!(moved("ab\n,c", (0, 0), "f,"), (0, 0));
+        assert_eq!(moved("a,b", (0, 0), "f;"), (0, 0));
+
+        assert_eq!(moved("ae\u{301},b", (0, 0), "f,"), (2, 0));
+    }
+}
@@ -1,159 +1,351 @@
-use super::{Editor, Mode};
+use crate::util::usize_sub;
+
+use super::{Editor, Mode, command::{self, Action, Command, Insert, Parse, Target}, key::Key, movement::Motion, operator::toggle_case, register::Registers};
+
+/// The last change made in normal mode, which `.` repeats.
+#[derive(Clone)]
+pub struct Repeat {
+    cmd: Command,
+    /// The keys typed in the insert session the command started, up to the `Esc` ending it, or in
+    /// the command line `!` opens, up to the `Enter` running the filter.
+    inserted: Vec<Key>,
+    /// Whether the insert session or the command line of the command is still going, so keys
+    /// are still recorded.
+    inserting: bool,
+}
 
 impl Editor {
-    pub(super) fn normal_mode(&mut self, event: sdl2::event::Event) {
-        match event {
-            sdl2::event::Event::KeyDown { keycode, .. } => {
-                match keycode {
-                    // Switch to normal mode
-                    Some(sdl2::keyboard::Keycode::Escape) => {
-                        self.set_mode(Mode::Normal);
-                    },
-                    // Move cursor 1 position to the left
-                    Some(sdl2::keyboard::Keycode::Left) => {
-                        self.move_left(1);
-                    },
-                    // Move cursor 1 position downward
-                    Some(sdl2::keyboard::Keycode::Down) => {
-                        self.move_down(1);
-                    },
-                    // Move cursor 1 position upward
-                    Some(sdl2::keyboard::Keycode::Up) => {
-                        self.move_up(1);
-                    },
-                    // Move cursor 1 position to the right
-                    Some(sdl2::keyboard::Keycode::Right) => {
-                        self.move_right(1);
-                    },
-                    _ => {},
-                };
+    pub(super) fn normal_mode(&mut self, key: Key) {
+        // Cancel the command being typed
+        if key == Key::Esc {
+            self.cmd_stack.clear();
+            return;
+        }
+
+        self.cmd_stack.push(key);
+
+        match command::parse(&self.cmd_stack) {
+            Parse::Pending => { },
+            Parse::Invalid => {
+                self.cmd_stack.clear();
+            },
+            Parse::Done(cmd) => {
+                self.cmd_stack.clear();
+                self.execute(cmd);
+            },
+        }
+    }
+
+    /// Executes a command typed in normal mode.
+    pub(super) fn execute(&mut self, mut cmd: Command) {
+        let n = cmd.count();
+
+        if cmd.register.is_some_and(|name| !Registers::is_valid(name)) {
+            return;
+        }
+
+        match &mut cmd.action {
+            Action::Move(motion) | Action::Operate(_, Target::Motion(motion)) => {
+                match self.resolve_find(*motion).and_then(|motion| self.resolve_search(motion)) {
+                    Some(resolved) => *motion = resolved,
+                    None => return,
+                }
+            },
+            _ => { },
+        }
+
+        if matches!(self.mode, Mode::Normal) && cmd.action.is_change() {
+            self.last_change = Some(Repeat { cmd, inserted: vec![], inserting: false });
+        }
+
+        match cmd.action {
+            Action::Move(motion) => {
+                if let Some((cx, cy)) = self.motion_target(motion, cmd.count) {
+                    self.cx = cx;
+                    self.cy = cy;
+                }
+            },
+            Action::Operate(operator, Target::Selection { lines }) => {
+                self.operate_selection(operator, lines, n, cmd.register);
+            },
+            Action::Operate(operator, target) => {
+                if let Some((range, linewise)) = self.target_range(operator, target, cmd.count) {
+                    self.operate(operator, range, linewise, cmd.register);
+                }
             },
-            sdl2::event::Event::TextInput { text, .. } => {
-                match text.as_str() {
+            Action::Insert(insert) => {
+                match insert {
                     // Switch to insert mode before the current character
-                    "i" => {
-                        self.move_left(1);
-                        self.set_mode(Mode::Insert);
-                    },
-                    // Switch to insert mode at the beginning of the line
-                    "I" => {
-                        self.move_left(0);
-                        self.set_mode(Mode::Insert);
-                    },
+                    Insert::Before => { },
                     // Switch to insert mode after the current character
-                    "a" => {
-                        self.set_mode(Mode::Insert);
+                    Insert::After => {
+                        self.cx = (self.cx + 1).min(self.get_line_width(0));
+                    },
+                    // Switch to insert mode before the first non-blank character of the line
+                    Insert::LineStart => {
+                        self.cx = self.first_non_blank(self.cy);
                     },
                     // Switch to insert mode at the end of the line
-                    "A" => {
+                    Insert::LineEnd => {
                         self.move_right(0);
-                        self.set_mode(Mode::Insert);
                     },
                     // Insert a new line below the current one and switch to insert mode
-                    "o" => {
+                    Insert::LineBelow => {
                         self.move_right(0);
                         self.insert_line();
-                        self.set_mode(Mode::Insert);
                     },
                     // Insert a new line above the current one and switch to insert mode
-                    "O" => {
+                    Insert::LineAbove => {
                         self.move_left(0);
                         self.insert_line();
                         self.move_up(1);
-                        self.set_mode(Mode::Insert);
-                    },
-                    // Switch to visual mode
-                    "v" => {
-                        self.set_mode(Mode::Visual);
-                    },
-                    // Switch to visual line mode
-                    "V" => {
-                        self.set_mode(Mode::Visual);
-                    },
-                    // Move cursor `n` positions to the left
-                    "h" => {
-                        let n = self.cmd_stack.parse::<usize>().unwrap_or(1);
-                        self.move_left(n);
-                        self.cmd_stack = "".to_string();
-                    },
-                    // Move cursor `n` positions downward
-                    "j" => {
-                        let n = self.cmd_stack.parse::<usize>().unwrap_or(1);
-                        self.move_down(n);
-                        self.cmd_stack = "".to_string();
                     },
-                    // Move cursor `n` positions upward
-                    "k" => {
-                        let n = self.cmd_stack.parse::<usize>().unwrap_or(1);
-                        self.move_up(n);
-                        self.cmd_stack = "".to_string();
-                    },
-                    // Move cursor `n` positions to the right
-                    "l" => {
-                        let n = self.cmd_stack.parse::<usize>().unwrap_or(1);
-                        self.move_right(n);
-                        self.cmd_stack = "".to_string();
-                    },
-                    // Move cursor to the beginning of the file if "gg"
-                    "g" => {
-                        if self.cmd_stack == "g" {
-                            self.move_up(0);
-                            self.move_left(0);
-                            self.cmd_stack = "".to_string();
-                        } else {
-                            self.stack_cmd(text.as_str());
-                        }
-                    },
-                    // Move cursor to the end of the file
-                    "G" => {
-                        self.move_down(0);
-                        self.move_right(0);
-                    },
-                    // Begin delete command or delete current line if "dd"
-                    "d" => {
-                        if self.cmd_stack == "d" {
-                            self.delete_line();
-                            self.cmd_stack = "".to_string();
-                        } else {
-                            self.stack_cmd(text.as_str());
-                        }
-                    },
-                    // Add the command to the stack for further processing
-                    _ => {
-                        self.stack_cmd(text.as_str());
-                    },
-                };
+                }
+
+                self.set_mode(Mode::Insert);
             },
-            _ => { },
-        };
-    }
+            Action::Visual(kind) => {
+                self.toggle_visual(kind);
+            },
+            Action::Reselect => {
+                self.reselect();
+            },
+            Action::SwapEnds { column } => {
+                self.swap_ends(column);
+            },
+            Action::Select(object) => {
+                self.select_object(object, cmd.count);
+            },
+            Action::Put { before, indent } => {
+                self.put(cmd.register, before, indent, n);
+            },
+            Action::Repeat => {
+                self.repeat_change(cmd.count);
+            },
+            Action::Search { forward } => {
+                self.start_search(forward);
+            },
+            Action::Ex => {
+                self.start_command(cmd.count);
+            },
+            Action::Record(register) => {
+                self.start_recording(register);
+            },
+            Action::Play(register) => {
+                self.run_macro(register, n);
+            },
+            Action::Mark(name) => {
+                self.set_mark(name);
+            },
+            Action::Scroll(scroll) => {
+                self.scroll(scroll, cmd.count);
+            },
+            Action::Align(align) => {
+                self.align(align);
+            },
+            Action::Undo => {
+                self.undo(n);
+            },
+            Action::Redo => {
+                self.redo(n);
+            },
+            Action::EarlierState => {
+                self.earlier_states(n);
+            },
+            Action::LaterState => {
+                self.later_states(n);
+            },
+            Action::Write => {
+                self.write();
+            },
+            Action::ToggleCase => {
+                let start = self.cursor_pos();
+                let end = self.buffer.grapheme_to_byte(self.cy, self.cx + n);
+                let text = self.buffer.slice(start..end).to_string();
+                let toggled: String = text.chars().map(toggle_case).collect();
+
+                if toggled != text {
+                    self.buffer_replace(start..end, &toggled);
+                }
 
-    fn stack_cmd(&mut self, cmd: &str) {
-        if let Some(first_char) = cmd.chars().next() {
-            self.cmd_stack.push(first_char);
+                self.cx = (self.cx + n).min(self.get_line_width(0));
+            },
+            Action::Join if matches!(self.mode, Mode::Visual(_)) => {
+                self.join_selection();
+            },
+            Action::Join => {
+                self.join_lines(n.max(2) - 1);
+            },
+            Action::Replace(c) if matches!(self.mode, Mode::Visual(_)) => {
+                self.replace_selection(c);
+            },
+            Action::Replace(c) => {
+                // Every character replaced must exist
+                if self.cx + n > self.get_line_width(0) {
+                    return;
+                }
+
+                let start = self.cursor_pos();
+                let end = self.buffer.grapheme_to_byte(self.cy, self.cx + n);
+                self.buffer_replace(start..end, &c.to_string().repeat(n));
+
+                self.cx += n - 1;
+            },
+        }
+
+        if let (Mode::Insert | Mode::Command, Some(change)) = (self.mode, &mut self.last_change) {
+            if change.cmd == cmd {
+                change.inserting = true;
+            }
+        }
+
+        if matches!(self.mode, Mode::Normal | Mode::Visual(_)) {
+            self.clamp_normal();
+
+            match cmd.action {
+                Action::Move(motion) if motion.is_vertical() => { },
+                Action::Scroll(_) => { },
+                // Stick to the end of the lines when moving vertically after "$"
+                Action::Move(Motion::LineEnd) => {
+                    self.cx_keep = usize::MAX;
+                },
+                _ => {
+                    self.cx_keep = self.cx;
+                },
+            }
         }
     }
 
-    fn delete_line(&mut self) {
-        // Check if the line exists
-        if self.cy >= self.lines.len() {
+    /// `.`, executes the last change again along with the text typed after it. A new `count`
+    /// replaces the count of the change, and inserts the text that many times. A numbered
+    /// register goes to the next one, so `"1p` followed by `.` puts the older deletes in order.
+    /// The change stays the last one, keeping the text it typed rather than recording it again.
+    fn repeat_change(&mut self, count: Option<usize>) {
+        let Some(Repeat { mut cmd, inserted, .. }) = self.last_change.clone() else {
             return;
+        };
+
+        if count.is_some() {
+            cmd.count = count;
         }
 
-        let start_pos = self.line();
-        let end_pos = start_pos + self.get_line_width(0);
+        let times = match cmd.action {
+            Action::Insert(_) => cmd.count(),
+            _ => 1,
+        };
+
+        if let (Action::Put { .. }, Some(register @ '1'..='8')) = (cmd.action, cmd.register) {
+            cmd.register = char::from_u32(register as u32 + 1);
+        }
+
+        self.execute(cmd);
 
-        self.content.drain(start_pos..=end_pos);
+        if let Some(change) = &mut self.last_change {
+            change.inserting = false;
+        }
+
+        match self.mode {
+            Mode::Insert => {
+                // The text is typed again for each time, on a line of its own after `o` and `O`
+                let (typed, esc) = match inserted.split_last() {
+                    Some((Key::Esc, typed)) => (typed, true),
+                    _ => (&inserted[..], false),
+                };
 
-        // Remove the line from the lines vector
-        self.lines.remove(self.cy);
+                for n in 0..times {
+                    if n > 0 && matches!(cmd.action, Action::Insert(Insert::LineBelow | Insert::LineAbove)) {
+                        self.insert_mode(Key::Enter);
+                    }
 
-        // If the deleted line was the last line, move the cursor up
-        if self.cy >= self.lines.len() {
-            self.cy -= 1;
-            if self.cx > self.lines[self.cy] as usize {
-                self.cx = self.lines[self.cy] as usize;
+                    for key in typed {
+                        self.insert_mode(*key);
+                    }
+                }
+
+                if esc {
+                    self.insert_mode(Key::Esc);
+                }
+            },
+            Mode::Command => {
+                for key in &inserted {
+                    self.command_mode(*key);
+                }
+            },
+            _ => { },
+        }
+
+        self.last_change = Some(Repeat { cmd, inserted, inserting: false });
+    }
+
+    /// Records a key typed in the insert session started by the last change, for `.`.
+    pub(super) fn record_insert(&mut self, key: Key) {
+        if let Some(change) = &mut self.last_change {
+            if change.inserting && key != Key::Ctrl('s') {
+                change.inserted.push(key);
+                change.inserting = key != Key::Esc && !(matches!(self.mode, Mode::Command) && key == Key::Enter);
             }
         }
     }
+
+    /// Keeps the cursor over a character of the line, as it cannot be past the end of the line
+    /// in normal mode.
+    pub(super) fn clamp_normal(&mut self) {
+        self.cx = self.cx.min(usize_sub(self.get_line_width(0), 1));
+    }
+
+    /// Joins the line of the cursor with the `n` lines below it, replacing the line breaks and
+    /// the indentation of the joined lines with a single space.
+    pub(super) fn join_lines(&mut self, n: usize) {
+        for _ in 0..n {
+            if self.cy >= self.content_lines() {
+                break;
+            }
+
+            let line = self.buffer.line(self.cy);
+            let trailing_blank = line.ends_with(' ') || line.ends_with('\t') || line.is_empty();
+
+            let next = self.cy + 1;
+            let start = self.buffer.line_to_byte(next) - 1;
+            let end = self.buffer.grapheme_to_byte(next, self.first_non_blank(next));
+            let next_empty = end == start + 1 + self.buffer.line(next).len();
+
+            self.cx = self.get_line_width(0);
+
+            let separator = if trailing_blank || next_empty || self.buffer.line(next).starts_with(')') { "" } else { " " };
+            self.buffer_replace(start..end, separator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str, keys: &str) -> Editor {
+        let mut editor = Editor::new(text);
+        for key in Key::parse(keys) {
+            editor.key(key);
+        }
+
+        editor
+    }
+
+    #[test]
+    fn repeat_keeps_the_text_typed() {
+        // Each "." inserts before the last character typed, where the cursor is left
+        let editor = typed("", "ifoo<Esc>3.2.");
+        assert_eq!(editor.buffer.to_string(), "fofoofoofofoofoooo");
+
+        let editor = typed("a\nb", "A!<Esc>j2.");
+        assert_eq!(editor.buffer.to_string(), "a!\nb!!");
+    }
+
+    #[test]
+    fn repeat_takes_a_new_count() {
+        let editor = typed("a b c d e", "dw2..");
+        assert_eq!(editor.buffer.to_string(), "");
+
+        let editor = typed("x\ny", "ox<Esc>2..");
+        assert_eq!(editor.buffer.to_string(), "x\nx\nx\nx\nx\nx\ny");
+    }
 }
@@ -0,0 +1,264 @@
+use std::ops::Range;
+
+use crate::util::usize_sub;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{Editor, Mode, command::Target, movement::{CharClass, Motion, MotionKind, Scanner}, register::RegisterKind};
+
+/// What `>` and `<` add to or remove from the beginning of each line.
+const INDENT: &str = "  ";
+
+/// An operator, which acts on the text covered by a motion or on whole lines.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operator {
+    /// `d`
+    Delete,
+    /// `c`
+    Change,
+    /// `y`
+    Yank,
+    /// `>`
+    Indent,
+    /// `<`
+    Dedent,
+    /// `gu`
+    Lowercase,
+    /// `gU`
+    Uppercase,
+    /// `g~`
+    ToggleCase,
+    /// `!`, filters lines through a command typed after it.
+    Filter,
+}
+
+impl Editor {
+    /// Returns the byte range of the text that `target` covers from the cursor for `operator`,
+    /// repeating it `count` times, and whether the range covers whole lines.
+    pub fn target_range(&self, operator: Operator, target: Target, count: Option<usize>) -> Option<(Range<usize>, bool)> {
+        let motion = match target {
+            Target::Line => {
+                let last = (self.cy + count.unwrap_or(1) - 1).min(self.content_lines());
+                return Some((self.lines_range(self.cy, last), true));
+            },
+            Target::Motion(motion) => motion,
+            Target::Object(object) => return self.object_range(object, count),
+            Target::Selection { lines } => return self.selection_range(lines),
+        };
+
+        // Like in vim, "cw" over a word changes only up to its end, leaving the blanks after it.
+        if let (Operator::Change, Motion::NextWord { big }) = (operator, motion) {
+            let on_word = self.buffer
+                .line(self.cy)
+                .graphemes(true)
+                .nth(self.cx)
+                .and_then(|g| g.chars().next())
+                .is_some_and(|c| CharClass::of(c, big) != CharClass::Blank);
+
+            if on_word {
+                let (ex, ey) = self.current_word_end(count.unwrap_or(1), big);
+                let range = self.cursor_pos()..self.buffer.grapheme_to_byte(ey, ex + 1);
+
+                return Some((range, false));
+            }
+        }
+
+        let (mut tx, mut ty) = self.motion_target(motion, count)?;
+
+        // Like in vim, "dw" over the last word of a line stops at the end of the line instead of
+        // going into the next one.
+        if let Motion::NextWord { big } = motion {
+            let mut scanner = Scanner::new(self, self.cx, self.cy, big);
+            for _ in 1..count.unwrap_or(1) {
+                scanner.next_word();
+            }
+
+            let (_, ly) = scanner.pos();
+            if ty > ly {
+                (tx, ty) = (self.buffer.line_width(ly), ly);
+            }
+        }
+
+        let ((sx, sy), (ex, ey)) = if (ty, tx) < (self.cy, self.cx) {
+            ((tx, ty), (self.cx, self.cy))
+        } else {
+            ((self.cx, self.cy), (tx, ty))
+        };
+
+        let range = match motion.kind() {
+            MotionKind::Linewise => return Some((self.lines_range(sy, ey), true)),
+            MotionKind::Inclusive => {
+                self.buffer.grapheme_to_byte(sy, sx)..self.buffer.grapheme_to_byte(ey, ex + 1)
+            },
+            // An exclusive motion that ends at the start of a line leaves the line break before
+            // it alone, and when it also starts before the text of its first line, it covers the
+            // lines in between as a whole, like "d}" does from the start of a paragraph.
+            MotionKind::Exclusive if ex == 0 && ey > sy => {
+                if sx <= self.first_non_blank(sy) {
+                    return Some((self.lines_range(sy, ey - 1), true));
+                }
+
+                self.buffer.grapheme_to_byte(sy, sx)..self.buffer.line_to_byte(ey) - 1
+            },
+            MotionKind::Exclusive => {
+                self.buffer.grapheme_to_byte(sy, sx)..self.buffer.grapheme_to_byte(ey, ex)
+            },
+        };
+
+        Some((range, false))
+    }
+
+    /// Returns the byte range of the lines `first..=last`, including the line break ending them.
+    pub fn lines_range(&self, first: usize, last: usize) -> Range<usize> {
+        let start = self.buffer.line_to_byte(first);
+        let end = if last < self.content_lines() {
+            self.buffer.line_to_byte(last + 1)
+        } else {
+            self.buffer.len_bytes()
+        };
+
+        start..end
+    }
+
+    /// Removes the lines `first..=last`, returning them with a line break after each one.
+    pub(super) fn remove_lines(&mut self, first: usize, last: usize) -> String {
+        let mut range = self.lines_range(first, last);
+        let mut text = self.buffer.slice(range.clone()).to_string();
+
+        if !text.ends_with('\n') {
+            text.push('\n');
+
+            // The last line has no line break of its own, so take the one before it.
+            range.start = range.start.saturating_sub(1);
+        }
+
+        self.buffer_remove(range);
+        text
+    }
+
+    /// Lowercases, uppercases or toggles the case of the text in `range`, for `operator`.
+    pub(super) fn convert_case(&mut self, operator: Operator, range: Range<usize>) {
+        let text = self.buffer.slice(range.clone());
+        let converted: String = match operator {
+            Operator::Lowercase => text.to_lowercase(),
+            Operator::Uppercase => text.to_uppercase(),
+            _ => text.chars().map(toggle_case).collect(),
+        };
+
+        if converted != text {
+            self.buffer_replace(range, &converted);
+        }
+    }
+
+    /// Applies `operator` to the text between the byte offsets of `range`, which covers whole
+    /// lines when `linewise` is set. Text deleted, changed or yanked goes into the register
+    /// `register`.
+    pub fn operate(&mut self, operator: Operator, range: Range<usize>, linewise: bool, register: Option<char>) {
+        let first = self.buffer.byte_to_line(range.start);
+        let last = self.buffer.byte_to_line(usize_sub(range.end, 1).max(range.start));
+
+        if let Operator::Delete | Operator::Change | Operator::Yank = operator {
+            let mut text = self.buffer.slice(range.clone()).to_string();
+            if linewise && !text.ends_with('\n') {
+                text.push('\n');
+            }
+
+            let kind = if linewise { RegisterKind::Line } else { RegisterKind::Char };
+            self.store(register, text, kind, operator == Operator::Yank);
+        }
+
+        match operator {
+            Operator::Delete => {
+                if linewise {
+                    self.remove_lines(first, last);
+
+                    self.cy = first.min(self.content_lines());
+                    self.cx = self.first_non_blank(self.cy);
+                } else {
+                    self.buffer_remove(range.clone());
+                    (self.cx, self.cy) = self.buffer.byte_to_cursor(range.start);
+                }
+            },
+            Operator::Change => {
+                let mut range = range;
+                if linewise && last < self.content_lines() {
+                    // Keep a line to type in.
+                    range.end -= 1;
+                }
+
+                self.buffer_remove(range.clone());
+                (self.cx, self.cy) = self.buffer.byte_to_cursor(range.start);
+
+                self.set_mode(Mode::Insert);
+            },
+            Operator::Yank => {
+                if linewise {
+                    self.cy = first;
+                } else {
+                    (self.cx, self.cy) = self.buffer.byte_to_cursor(range.start);
+                }
+            },
+            Operator::Indent | Operator::Dedent => {
+                for n in first..=last {
+                    let start = self.buffer.line_to_byte(n);
+                    let line = self.buffer.line(n);
+
+                    if operator == Operator::Indent {
+                        if !line.is_empty() {
+                            self.buffer_insert(start, INDENT);
+                        }
+                    } else {
+                        let width = if line.starts_with('\t') {
+                            1
+                        } else {
+                            line.bytes().take(INDENT.len()).take_while(|b| *b == b' ').count()
+                        };
+
+                        if width > 0 {
+                            self.buffer_remove(start..start + width);
+                        }
+                    }
+                }
+
+                self.cy = first;
+                self.cx = self.first_non_blank(first);
+            },
+            Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+                self.convert_case(operator, range.clone());
+
+                if linewise {
+                    self.cy = first;
+                } else {
+                    (self.cx, self.cy) = self.buffer.byte_to_cursor(range.start);
+                }
+            },
+            // The command is typed after the range of the lines, like in `:.,.+2!sort`
+            Operator::Filter => {
+                let range = match last - first {
+                    0 => ".".to_string(),
+                    n => format!(".,.+{n}"),
+                };
+
+                self.cy = first;
+                self.prompt.set(&format!("{range}!"));
+                self.set_mode(Mode::Command);
+            },
+        }
+    }
+}
+
+/// Switches the case of `c`. Characters that change length when switching case are kept as is, so
+/// the text keeps its shape.
+pub fn toggle_case(c: char) -> String {
+    let toggled: String = if c.is_lowercase() {
+        c.to_uppercase().collect()
+    } else {
+        c.to_lowercase().collect()
+    };
+
+    if toggled.chars().count() == 1 {
+        toggled
+    } else {
+        c.to_string()
+    }
+}
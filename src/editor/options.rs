@@ -0,0 +1,127 @@
+use super::Editor;
+
+/// The options changed with `:set`.
+pub struct Options {
+    /// Highlights the matches of the last search.
+    pub hlsearch: bool,
+    /// Moves the cursor to the first match of a search while it is typed.
+    pub incsearch: bool,
+    /// How many lines are kept above and below the cursor when scrolling.
+    pub scrolloff: usize,
+    /// How many columns are kept left and right of the cursor when scrolling sideways.
+    pub sidescrolloff: usize,
+    /// Shows the number of each line in the gutter.
+    pub number: bool,
+    /// Shows how far each line is from the cursor's in the gutter, or only for the other lines
+    /// along with 'number'.
+    pub relativenumber: bool,
+    /// Lays out a line longer than the screen across as many rows as it takes, breaking it after
+    /// a blank when one fits.
+    pub wrap: bool,
+    /// Indents the rows continuing a wrapped line as much as its first one.
+    pub breakindent: bool,
+    /// The text shown at the start of the rows continuing a wrapped line.
+    pub showbreak: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            hlsearch: true,
+            incsearch: true,
+            scrolloff: 5,
+            sidescrolloff: 0,
+            number: true,
+            relativenumber: false,
+            wrap: false,
+            breakindent: false,
+            showbreak: String::new(),
+        }
+    }
+}
+
+/// The names of the options, with their short names, which `:set` completes.
+pub const OPTIONS: &[(&str, &str)] = &[
+    ("breakindent", "bri"),
+    ("hlsearch", "hls"),
+    ("incsearch", "is"),
+    ("number", "nu"),
+    ("relativenumber", "rnu"),
+    ("scrolloff", "so"),
+    ("showbreak", "sbr"),
+    ("sidescrolloff", "siso"),
+    ("wrap", "wrap"),
+];
+
+/// Returns the full name of the option typed as `name`, if there is one.
+fn option_name(name: &str) -> Option<&'static str> {
+    OPTIONS
+        .iter()
+        .find(|(option, short)| name == *option || name == *short)
+        .map(|(option, _)| *option)
+}
+
+impl Options {
+    /// Returns the option `name` when it is turned on and off rather than set to a value.
+    fn flag(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "hlsearch" => Some(&mut self.hlsearch),
+            "incsearch" => Some(&mut self.incsearch),
+            "number" => Some(&mut self.number),
+            "relativenumber" => Some(&mut self.relativenumber),
+            "wrap" => Some(&mut self.wrap),
+            "breakindent" => Some(&mut self.breakindent),
+            _ => None,
+        }
+    }
+
+    /// Sets the option `name`, which takes a value, to `value`.
+    fn set_value(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let number = || value.parse::<usize>().map_err(|_| format!("Number required: {name}={value}"));
+
+        match name {
+            "scrolloff" => self.scrolloff = number()?,
+            "sidescrolloff" => self.sidescrolloff = number()?,
+            "showbreak" => self.showbreak = value.to_string(),
+            _ => return Err(format!("Invalid argument: {name}={value}")),
+        }
+
+        Ok(())
+    }
+
+    /// Sets an option as typed after `:set`: `name` and `noname` turn it on and off, `name!` and
+    /// `invname` toggle it and `name=value` sets it to `value`.
+    fn set(&mut self, arg: &str) -> Result<(), String> {
+        if let Some((name, value)) = arg.split_once('=') {
+            let name = option_name(name).ok_or_else(|| format!("Unknown option: {name}"))?;
+            return self.set_value(name, value);
+        }
+
+        let (name, value) = if let Some(name) = arg.strip_suffix('!') {
+            (name, None)
+        } else if let Some(name) = arg.strip_prefix("inv").filter(|name| option_name(name).is_some()) {
+            (name, None)
+        } else if let Some(name) = arg.strip_prefix("no").filter(|name| option_name(name).is_some()) {
+            (name, Some(false))
+        } else {
+            (arg, Some(true))
+        };
+
+        let name = option_name(name).ok_or_else(|| format!("Unknown option: {arg}"))?;
+        let flag = self.flag(name).ok_or_else(|| format!("Argument required: {arg}"))?;
+        *flag = value.unwrap_or(!*flag);
+
+        Ok(())
+    }
+}
+
+impl Editor {
+    /// `:set {option}...`, sets each option of `args`.
+    pub(super) fn set_options(&mut self, args: &str) -> Result<(), String> {
+        for arg in args.split_whitespace() {
+            self.options.set(arg)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use super::key::Key;
+
+/// How many lines of each kind the history keeps.
+const HISTORY_SIZE: usize = 100;
+
+/// A line of text typed at the bottom of the window, like the pattern of a search.
+#[derive(Default)]
+pub struct Prompt {
+    pub text: String,
+    /// The position of the cursor in the text, in characters.
+    pub cursor: usize,
+    /// While going through the history with Up and Down, the entry shown and the text typed
+    /// before, which the entries shown start with.
+    browse: Option<(usize, String)>,
+}
+
+impl Prompt {
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.browse = None;
+    }
+
+    /// Replaces the text with `text`, moving the cursor to its end.
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = text.chars().count();
+        self.browse = None;
+    }
+
+    /// Replaces the text between the character `start` and the cursor with `text`, moving the
+    /// cursor to its end.
+    pub fn replace(&mut self, start: usize, text: &str) {
+        let (start, end) = (self.byte(start), self.byte(self.cursor));
+        self.text.replace_range(start..end, text);
+        self.cursor = self.text[..start + text.len()].chars().count();
+    }
+
+    /// Shows the entry of `history` before the one shown, or after it when `older` is `false`,
+    /// skipping the ones that do not start with what was typed. Going past the last entry shows
+    /// what was typed again. Returns `false` when there is no entry to go to.
+    pub fn browse(&mut self, history: &[String], older: bool) -> bool {
+        let (index, typed) = self.browse.get_or_insert_with(|| (history.len(), self.text.clone()));
+
+        let found = if older {
+            history[..*index].iter().rposition(|entry| entry.starts_with(typed.as_str()))
+        } else {
+            history.iter().skip(*index + 1).position(|entry| entry.starts_with(typed.as_str())).map(|n| *index + 1 + n)
+        };
+
+        let text = match found {
+            Some(n) => {
+                *index = n;
+                history[n].clone()
+            },
+            None if !older && *index < history.len() => {
+                *index = history.len();
+                typed.clone()
+            },
+            None => return false,
+        };
+
+        self.text = text;
+        self.cursor = self.text.chars().count();
+        true
+    }
+
+    /// Edits the text with `key`, returning `false` when the key does not edit text.
+    pub fn edit(&mut self, key: Key) -> bool {
+        match key {
+            Key::Char(c) => {
+                self.text.insert(self.byte(self.cursor), c);
+                self.cursor += 1;
+                self.browse = None;
+            },
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.byte(self.cursor));
+                self.browse = None;
+            },
+            Key::Delete if self.cursor < self.text.chars().count() => {
+                self.text.remove(self.byte(self.cursor));
+                self.browse = None;
+            },
+            Key::Left => {
+                self.cursor = self.cursor.saturating_sub(1);
+            },
+            Key::Right => {
+                self.cursor = (self.cursor + 1).min(self.text.chars().count());
+            },
+            Key::Backspace | Key::Delete => { },
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Returns the byte offset of the character `n` of the text.
+    fn byte(&self, n: usize) -> usize {
+        self.text.char_indices().nth(n).map_or(self.text.len(), |(i, _)| i)
+    }
+}
+
+/// The commands and searches typed before, oldest first, which are kept in a file between
+/// sessions.
+#[derive(Default)]
+pub struct PromptHistory {
+    pub commands: Vec<String>,
+    pub searches: Vec<String>,
+    /// The file the history is kept in, if any.
+    path: Option<PathBuf>,
+}
+
+impl PromptHistory {
+    /// Returns the file the history is kept in by default, in the data directory of the user.
+    pub fn default_path() -> Option<PathBuf> {
+        let data = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+
+        Some(data.join("echo/history"))
+    }
+
+    /// Reads the history kept in `path`, which is written back there as it grows. Commands are
+    /// kept in lines starting with `:` and searches in lines starting with `/`.
+    pub fn load(path: PathBuf) -> Self {
+        let mut history = Self::default();
+
+        if let Ok(text) = fs::read_to_string(&path) {
+            for line in text.lines() {
+                if let Some(command) = line.strip_prefix(':') {
+                    add(&mut history.commands, command);
+                } else if let Some(search) = line.strip_prefix('/') {
+                    add(&mut history.searches, search);
+                }
+            }
+        }
+
+        history.path = Some(path);
+        history
+    }
+
+    pub fn add_command(&mut self, line: &str) -> Result<(), String> {
+        add(&mut self.commands, line);
+        self.save()
+    }
+
+    pub fn add_search(&mut self, line: &str) -> Result<(), String> {
+        add(&mut self.searches, line);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let write = || -> io::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+
+            let mut text = String::new();
+            for command in &self.commands {
+                text.push_str(&format!(":{command}\n"));
+            }
+            for search in &self.searches {
+                text.push_str(&format!("/{search}\n"));
+            }
+
+            fs::write(path, text)
+        };
+
+        write().map_err(|e| format!("Cannot write the history to {}: {e}", path.display()))
+    }
+}
+
+/// Adds `line` as the newest entry of `entries`, dropping an older entry with the same text and
+/// the oldest one when there are too many.
+fn add(entries: &mut Vec<String>, line: &str) {
+    if line.is_empty() {
+        return;
+    }
+
+    entries.retain(|entry| entry != line);
+    entries.push(line.to_string());
+
+    if entries.len() > HISTORY_SIZE {
+        entries.remove(0);
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use super::{Editor, Mode, operator::Operator};
+
+/// The most text a put can make, like in vim, so a count too big fails rather than running out
+/// of memory.
+const MAX_PUT: usize = i32::MAX as usize;
+
+/// How the text of a register was taken, which decides how it is put back.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RegisterKind {
+    /// Part of a line or of several lines, put inside the line of the cursor.
+    Char,
+    /// Whole lines, each ending with a line break, put between the lines.
+    Line,
+    /// The columns of a block selection, one per line, put as a block at the cursor.
+    Block,
+}
+
+/// Text stored in a register.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Register {
+    pub text: String,
+    pub kind: RegisterKind,
+}
+
+/// Every register text can be yanked into or put from, following vim:
+///
+/// - `"`, the unnamed register, holds the text of the last yank or delete;
+/// - `0` holds the last yanked text, and `1` to `9` the last deleted lines, shifting on every
+///   delete;
+/// - `a` to `z` are set by naming them, and `A` to `Z` append to them;
+/// - `-` holds the last delete within a line;
+/// - `+` is the system clipboard and `*` the primary selection, see [`Editor::register`];
+/// - `_` discards everything written to it.
+#[derive(Default)]
+pub struct Registers {
+    unnamed: Option<Register>,
+    registers: HashMap<char, Register>,
+}
+
+impl Registers {
+    /// Whether `name` is a register that can be typed after `"`.
+    pub fn is_valid(name: char) -> bool {
+        matches!(name, '"' | '0'..='9' | 'a'..='z' | 'A'..='Z' | '-' | '+' | '*' | '_')
+    }
+
+    pub fn get(&self, name: char) -> Option<&Register> {
+        match name {
+            '"' => self.unnamed.as_ref(),
+            _ => self.registers.get(&name.to_ascii_lowercase()),
+        }
+    }
+
+    /// Stores yanked text into the register `name`, or into `0` when there is none.
+    pub fn yank(&mut self, name: Option<char>, register: Register) {
+        self.set(name.filter(|&name| name != '"').unwrap_or('0'), register);
+    }
+
+    /// Stores deleted text into the register `name`. When there is none, deletes within a line go
+    /// into `-`, and the others into `1`, shifting the older ones up to `9`.
+    pub fn delete(&mut self, name: Option<char>, register: Register) {
+        match name.filter(|&name| name != '"') {
+            Some(name) => self.set(name, register),
+            None if register.kind == RegisterKind::Char && !register.text.contains('\n') => {
+                self.set('-', register);
+            },
+            None => {
+                for n in (1..9).rev() {
+                    let from = char::from_digit(n, 10).unwrap();
+                    let to = char::from_digit(n + 1, 10).unwrap();
+
+                    if let Some(shifted) = self.registers.remove(&from) {
+                        self.registers.insert(to, shifted);
+                    }
+                }
+
+                self.set('1', register);
+            },
+        }
+    }
+
+    /// Stores a recorded macro into `name`, which unlike yanks and deletes leaves the unnamed
+    /// register alone.
+    pub fn set_macro(&mut self, name: char, register: Register) {
+        let unnamed = self.unnamed.take();
+        self.set(name, register);
+        self.unnamed = unnamed;
+    }
+
+    /// Stores `register` into `name`, which also becomes the content of the unnamed register.
+    /// Uppercase names append to their lowercase register, which becomes linewise when either
+    /// part is.
+    pub fn set(&mut self, name: char, register: Register) {
+        if name == '_' {
+            return;
+        }
+
+        let register = match (name.is_ascii_uppercase(), self.registers.remove(&name.to_ascii_lowercase())) {
+            (true, Some(old)) => match (old.kind, register.kind) {
+                (RegisterKind::Line, RegisterKind::Char) => Register {
+                    text: old.text + &register.text + "\n",
+                    kind: RegisterKind::Line,
+                },
+                (RegisterKind::Char, RegisterKind::Line) => Register {
+                    text: old.text + "\n" + &register.text,
+                    kind: RegisterKind::Line,
+                },
+                (kind, _) => Register {
+                    text: old.text + &register.text,
+                    kind,
+                },
+            },
+            _ => register,
+        };
+
+        self.unnamed = Some(register.clone());
+        self.registers.insert(name.to_ascii_lowercase(), register);
+    }
+}
+
+impl Editor {
+    /// Returns the content of the register `name`. The `+` register reads the system clipboard
+    /// and the `*` register the primary selection, whose text is linewise when it ends with a line
+    /// break, unless it is what was last stored there from the editor.
+    pub fn register(&self, name: char) -> Option<Register> {
+        let stored = self.registers.get(name).cloned();
+
+        if !matches!(name, '+' | '*') {
+            return stored;
+        }
+
+        let text = self.clipboard.as_ref().and_then(|clipboard| match name {
+            '*' => clipboard.primary_selection_text().ok(),
+            _ => clipboard.clipboard_text().ok(),
+        });
+
+        let Some(text) = text else {
+            return stored;
+        };
+        let text = normalize_line_endings(&text);
+
+        match stored {
+            Some(stored) if stored.text == text => Some(stored),
+            _ if text.is_empty() => None,
+            _ => {
+                let kind = if text.ends_with('\n') { RegisterKind::Line } else { RegisterKind::Char };
+                Some(Register { text, kind })
+            },
+        }
+    }
+
+    /// Puts the text of the register `name`, or of the unnamed one, `n` times after the cursor, or
+    /// before it when `before` is set. With `indent` set, like in `]p`, lines are put with the
+    /// indentation of the cursor's line.
+    pub(super) fn put(&mut self, name: Option<char>, before: bool, indent: bool, n: usize) {
+        let Some(register) = self.register(name.unwrap_or('"')) else {
+            return;
+        };
+
+        if register.text.len().checked_mul(n).is_none_or(|len| len > MAX_PUT) {
+            self.message = Some("Resulting text too long".to_string());
+            return;
+        }
+
+        if let Mode::Visual(_) = self.mode {
+            self.put_over_selection(register, before, n);
+            return;
+        }
+
+        match register.kind {
+            RegisterKind::Char => self.put_chars(&register.text.repeat(n), before),
+            RegisterKind::Line => {
+                let text = if indent { self.reindent(&register.text) } else { register.text };
+                self.put_lines(&text.repeat(n), before);
+            },
+            RegisterKind::Block => self.put_block(&register.text, before, n),
+        }
+    }
+
+    /// Puts `text` inside the line of the cursor, leaving the cursor over its last character, or
+    /// over its first one when it goes through several lines.
+    fn put_chars(&mut self, text: &str, before: bool) {
+        let pos = if before || self.get_line_width(0) == 0 {
+            self.cursor_pos()
+        } else {
+            self.buffer.grapheme_to_byte(self.cy, self.cx + 1)
+        };
+
+        self.buffer_insert(pos, text);
+
+        if text.contains('\n') {
+            (self.cx, self.cy) = self.buffer.byte_to_cursor(pos);
+        } else {
+            (self.cx, self.cy) = self.buffer.byte_to_cursor(pos + text.len());
+            self.cx = self.cx.saturating_sub(1);
+        }
+    }
+
+    /// Puts the lines of `text` below the cursor's line, or above it when `before` is set, leaving
+    /// the cursor at the first non-blank character of the first of them.
+    pub(super) fn put_lines(&mut self, text: &str, before: bool) {
+        let first = if before { self.cy } else { self.cy + 1 };
+
+        if first <= self.content_lines() {
+            self.buffer_insert(self.buffer.line_to_byte(first), text);
+        } else {
+            // Past the end of the buffer, the lines go after a line break ending the last one.
+            let text = format!("\n{}", text.strip_suffix('\n').unwrap_or(text));
+            self.buffer_insert(self.buffer.len_bytes(), &text);
+        }
+
+        self.cy = first;
+        self.cx = self.first_non_blank(first);
+    }
+
+    /// Puts every line of `text` at the cursor's column in the lines from the cursor's, adding
+    /// lines and padding them with spaces where they are too short.
+    fn put_block(&mut self, text: &str, before: bool, n: usize) {
+        let col = if before || self.get_line_width(0) == 0 { self.cx } else { self.cx + 1 };
+
+        for (i, line) in text.split('\n').enumerate() {
+            let y = self.cy + i;
+            if y > self.content_lines() {
+                self.buffer_insert(self.buffer.len_bytes(), "\n");
+            }
+
+            let width = self.buffer.line_width(y);
+            if width < col {
+                let end = self.buffer.grapheme_to_byte(y, width);
+                self.buffer_insert(end, &" ".repeat(col - width));
+            }
+
+            self.buffer_insert(self.buffer.grapheme_to_byte(y, col), &line.repeat(n));
+        }
+
+        self.cx = col;
+    }
+
+    /// Replaces the indentation of the lines of `text` so the first one gets the indentation of
+    /// the cursor's line, keeping the others where they were relative to it.
+    fn reindent(&self, text: &str) -> String {
+        let indentation = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+
+        let line = self.buffer.line(self.cy);
+        let target = &line[..indentation(&line)];
+        let first = text.lines().next().map_or(0, indentation);
+
+        text.lines()
+            .map(|line| {
+                let strip = indentation(line).min(first);
+                if line[strip..].is_empty() {
+                    "\n".to_string()
+                } else {
+                    format!("{target}{}\n", &line[strip..])
+                }
+            })
+            .collect()
+    }
+
+    /// `p` and `P` in visual mode, which replace the selection with `register`. The selection
+    /// goes into the unnamed register with `p`, but not with `P`.
+    fn put_over_selection(&mut self, register: Register, before: bool, n: usize) {
+        let Some((range, linewise)) = self.selection_range(false) else {
+            return;
+        };
+
+        let first = self.buffer.byte_to_line(range.start);
+        let target = if before { Some('_') } else { None };
+
+        self.exit_visual();
+        self.operate(Operator::Delete, range.clone(), linewise, target);
+
+        match (linewise, register.kind) {
+            (true, kind) => {
+                let mut text = register.text;
+                if kind != RegisterKind::Line {
+                    text.push('\n');
+                }
+
+                // When the selection went to the end of the buffer, the cursor is above it.
+                self.put_lines(&text.repeat(n), first <= self.content_lines());
+            },
+            (false, RegisterKind::Line) => {
+                let pos = self.cursor_pos();
+                self.buffer_insert(pos, &format!("\n{}", register.text.repeat(n)));
+
+                self.cy += 1;
+                self.cx = self.first_non_blank(self.cy);
+            },
+            (false, RegisterKind::Char) => self.put_chars(&register.text.repeat(n), true),
+            (false, RegisterKind::Block) => self.put_block(&register.text, true, n),
+        }
+    }
+
+    /// Writes `text` to the system clipboard for the `+` register, or to the primary selection
+    /// for the `*` register.
+    pub(super) fn set_clipboard(&mut self, name: char, text: &str) {
+        let result = self.clipboard.as_ref().map(|clipboard| match name {
+            '*' => clipboard.set_primary_selection_text(text),
+            _ => clipboard.set_clipboard_text(text),
+        });
+
+        if let Some(Err(e)) = result {
+            self.message = Some(e);
+        }
+    }
+
+    /// Stores the text taken by an operator into the registers, as deleted text unless it was
+    /// yanked.
+    pub(super) fn store(&mut self, name: Option<char>, text: String, kind: RegisterKind, yank: bool) {
+        if let Some(name @ ('+' | '*')) = name {
+            self.set_clipboard(name, &text);
+        }
+
+        let register = Register { text, kind };
+
+        if yank {
+            self.registers.yank(name, register);
+        } else {
+            self.registers.delete(name, register);
+        }
+    }
+}
+
+/// Turns the `\r\n` and `\r` line breaks of text coming from outside the editor into `\n`.
+pub fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::key::Key;
+    use super::*;
+
+    #[test]
+    fn put_counts_too_big_fail() {
+        let mut editor = Editor::new("a");
+        for key in Key::parse("yy18446744073709551615p") {
+            editor.key(key);
+        }
+
+        assert_eq!(editor.buffer.to_string(), "a");
+        assert_eq!(editor.message.as_deref(), Some("Resulting text too long"));
+    }
+}
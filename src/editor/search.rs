@@ -0,0 +1,182 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{Editor, Mode, movement::{CharClass, Motion}};
+
+/// The last search made, which `n` and `N` repeat.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Search {
+    pub pattern: String,
+    pub forward: bool,
+}
+
+/// A regular expression to search for, matched within each line.
+///
+/// Patterns follow the syntax of the `regex` crate, plus `\<` and `\>` for the start and end of
+/// words. Matching ignores case unless the pattern has uppercase letters, while `\c` and `\C`
+/// anywhere in the pattern make it ignore or match case regardless.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    regex: Regex,
+}
+
+impl Pattern {
+    /// Returns `None` for empty and invalid patterns.
+    pub fn new(pattern: &str) -> Option<Self> {
+        Self::with_case(pattern, None)
+    }
+
+    /// Like [`Pattern::new`], but `ignore_case`, when given, decides whether case is ignored
+    /// instead of the pattern.
+    pub fn with_case(pattern: &str, ignore_case: Option<bool>) -> Option<Self> {
+        let mut text = String::new();
+        let mut upper = false;
+        let mut flag = None;
+
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                upper |= c.is_uppercase();
+                text.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('c') => flag = flag.or(Some(true)),
+                Some('C') => flag = Some(false),
+                Some('<' | '>') => text.push_str("\\b"),
+                Some(c) => {
+                    text.push('\\');
+                    text.push(c);
+                },
+                None => text.push_str("\\\\"),
+            }
+        }
+
+        if text.is_empty() {
+            return None;
+        }
+
+        let ignore_case = ignore_case.or(flag).unwrap_or(!upper);
+        let regex = RegexBuilder::new(&text).case_insensitive(ignore_case).build().ok()?;
+
+        Some(Self { regex })
+    }
+
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Returns the byte ranges of the matches in `line`, which do not overlap.
+    pub fn find_iter(&self, line: &str) -> Vec<Range<usize>> {
+        self.regex.find_iter(line).map(|m| m.range()).collect()
+    }
+}
+
+impl Editor {
+    /// Returns where the `n`th match of `pattern` starts, as `(cx, cy)`, searching forward or
+    /// backward from `from` and wrapping around the ends of the buffer.
+    pub fn search(&self, pattern: &Pattern, from: (usize, usize), forward: bool, n: usize) -> Option<(usize, usize)> {
+        let lines = self.buffer.len_lines();
+        let (mut cx, mut cy) = from;
+
+        for _ in 0..n {
+            let mut found = None;
+
+            // The line of the cursor comes up twice: first for the matches on one side of the
+            // cursor, and last, after wrapping around, for the ones on the other side.
+            for step in 0..=lines {
+                let y = if forward { (cy + step) % lines } else { (cy + lines - step % lines) % lines };
+                let line = self.buffer.line(y);
+
+                let columns = self.match_columns(pattern, &line);
+                let column = match (step, forward) {
+                    (0, true) => columns.into_iter().find(|&x| x > cx),
+                    (0, false) => columns.into_iter().rev().find(|&x| x < cx),
+                    (_, true) => columns.into_iter().next(),
+                    (_, false) => columns.into_iter().next_back(),
+                };
+
+                if let Some(x) = column {
+                    found = Some((x, y));
+                    break;
+                }
+            }
+
+            (cx, cy) = found?;
+        }
+
+        Some((cx, cy))
+    }
+
+    /// Returns the columns where the matches of `pattern` in `line` start.
+    fn match_columns(&self, pattern: &Pattern, line: &str) -> Vec<usize> {
+        pattern
+            .find_iter(line)
+            .into_iter()
+            .map(|range| line[..range.start].graphemes(true).count())
+            .collect()
+    }
+
+    /// Returns the columns of the line `n` covered by the matches to highlight.
+    pub fn search_highlights(&self, n: usize) -> Vec<Range<usize>> {
+        let Some(pattern) = &self.highlight else {
+            return vec![];
+        };
+
+        // The pattern being typed is highlighted even without 'hlsearch'
+        if !self.options.hlsearch && !matches!(self.mode, Mode::Search { .. } | Mode::Command) {
+            return vec![];
+        }
+
+        let line = self.buffer.line(n);
+        pattern
+            .find_iter(&line)
+            .into_iter()
+            .map(|range| {
+                let start = line[..range.start].graphemes(true).count();
+                start..start + line[range].graphemes(true).count()
+            })
+            .collect()
+    }
+
+    /// Makes `pattern` the last search, which gets highlighted.
+    pub(super) fn set_search(&mut self, pattern: String, forward: bool) {
+        self.highlight = Pattern::new(&pattern);
+        self.last_search = Some(Search { pattern, forward });
+    }
+
+    /// Turns `*` and `#` into a search for the word under or after the cursor, which becomes the
+    /// last search. Returns `None` when there is no word after the cursor in its line.
+    pub fn resolve_search(&mut self, motion: Motion) -> Option<Motion> {
+        let Motion::SearchWord { forward } = motion else {
+            return Some(motion);
+        };
+
+        let text = self.buffer.line(self.cy);
+        let line: Vec<&str> = text.graphemes(true).collect();
+        let keyword = |g: &str| g.chars().next().is_some_and(|c| CharClass::of(c, false) == CharClass::Keyword);
+
+        let mut start = (self.cx..line.len()).find(|&x| keyword(line[x]))?;
+        while start > 0 && keyword(line[start - 1]) {
+            start -= 1;
+        }
+
+        let mut end = start;
+        while end < line.len() && keyword(line[end]) {
+            end += 1;
+        }
+
+        let word = line[start..end].concat();
+        self.set_search(format!("\\<{word}\\>"), forward);
+
+        // Searching backward from the middle of the word would stop at its start.
+        if !forward {
+            self.cx = start;
+        }
+
+        Some(Motion::SearchNext { reverse: false })
+    }
+}
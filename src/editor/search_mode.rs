@@ -0,0 +1,81 @@
+use super::{Editor, Mode, key::Key, search::Pattern};
+
+impl Editor {
+    pub(super) fn search_mode(&mut self, key: Key) {
+        let Mode::Search { forward, origin } = self.mode else {
+            return;
+        };
+
+        match key {
+            // Cancel the search, going back to where it started
+            Key::Esc => {
+                self.cancel_search(origin);
+            },
+            Key::Backspace if self.prompt.text.is_empty() => {
+                self.cancel_search(origin);
+            },
+            // Search for the pattern typed, or for the last one again when nothing was typed
+            Key::Enter => {
+                let pattern = match self.prompt.text.as_str() {
+                    "" => self.last_search.as_ref().map(|search| search.pattern.clone()),
+                    text => Some(text.to_string()),
+                };
+
+                (self.cx, self.cy) = origin;
+                self.set_mode(Mode::Normal);
+
+                if let Err(e) = self.prompt_history.add_search(&self.prompt.text) {
+                    self.message = Some(e);
+                }
+
+                if let Some(pattern) = pattern {
+                    self.set_search(pattern.clone(), forward);
+
+                    if let Some((cx, cy)) = Pattern::new(&pattern).and_then(|p| self.search(&p, origin, forward, 1)) {
+                        (self.cx, self.cy) = (cx, cy);
+                    }
+                }
+
+                self.cx_keep = self.cx;
+            },
+            Key::Up | Key::Down => {
+                if self.prompt.browse(&self.prompt_history.searches, key == Key::Up) {
+                    self.preview_search(forward, origin);
+                }
+            },
+            key => {
+                if self.prompt.edit(key) {
+                    self.preview_search(forward, origin);
+                }
+            },
+        }
+    }
+
+    /// Starts typing a search with `/`, or with `?` when it goes backward.
+    pub(super) fn start_search(&mut self, forward: bool) {
+        self.prompt.clear();
+        self.set_mode(Mode::Search { forward, origin: (self.cx, self.cy) });
+    }
+
+    /// Moves the cursor to the first match of the pattern typed so far, highlighting every
+    /// match of it, unless 'incsearch' is off.
+    fn preview_search(&mut self, forward: bool, origin: (usize, usize)) {
+        if !self.options.incsearch {
+            return;
+        }
+
+        self.highlight = Pattern::new(&self.prompt.text);
+
+        (self.cx, self.cy) = self.highlight
+            .as_ref()
+            .and_then(|pattern| self.search(pattern, origin, forward, 1))
+            .unwrap_or(origin);
+    }
+
+    fn cancel_search(&mut self, origin: (usize, usize)) {
+        (self.cx, self.cy) = origin;
+        self.highlight = self.last_search.as_ref().and_then(|search| Pattern::new(&search.pattern));
+
+        self.set_mode(Mode::Normal);
+    }
+}
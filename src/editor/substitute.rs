@@ -0,0 +1,384 @@
+use std::{collections::VecDeque, ops::Range};
+
+use regex::Regex;
+
+use super::{Editor, Mode, buffer::Buffer, ex::split_delimited, history::Edit, key::Key, search::Pattern};
+
+/// A substitution typed as `:s/pattern/replacement/flags`, before it runs.
+struct Substitute {
+    pattern: String,
+    /// `None` while only the pattern has been typed.
+    replacement: Option<String>,
+    /// `g`, replaces every match in each line instead of only the first one.
+    global: bool,
+    /// `c`, asks before replacing each match.
+    confirm: bool,
+    /// `i` and `I`, ignore or match case regardless of the pattern.
+    ignore_case: Option<bool>,
+}
+
+impl Substitute {
+    /// Parses what follows `:s`. Any character that is not a letter, a digit or a blank can
+    /// separate the pattern from the replacement in place of `/`.
+    fn parse(args: &str) -> Result<Self, String> {
+        let mut chars = args.chars();
+
+        let delimiter = chars.next().ok_or("Missing pattern")?;
+        if delimiter.is_alphanumeric() || delimiter.is_whitespace() || delimiter == '\\' {
+            return Err("Invalid delimiter".to_string());
+        }
+
+        let (pattern, rest) = split_delimited(chars.as_str(), delimiter);
+        let (replacement, flags) = match rest {
+            Some(rest) => {
+                let (replacement, flags) = split_delimited(rest, delimiter);
+                (Some(replacement), flags.unwrap_or(""))
+            },
+            None => (None, ""),
+        };
+
+        let mut substitute = Self {
+            pattern,
+            replacement,
+            global: false,
+            confirm: false,
+            ignore_case: None,
+        };
+
+        for flag in flags.trim_end().chars() {
+            match flag {
+                'g' => substitute.global = true,
+                'c' => substitute.confirm = true,
+                'i' => substitute.ignore_case = Some(true),
+                'I' => substitute.ignore_case = Some(false),
+                _ => return Err(format!("Invalid flag: {flag}")),
+            }
+        }
+
+        Ok(substitute)
+    }
+}
+
+/// Turns a replacement as typed into a template for [`regex::Captures::expand`].
+///
+/// `\1` to `\9` and `$1` to `$9` are replaced with the text of the capture groups, `&` and `$0`
+/// with the whole match, and `\r` and `\n` with a line break. `\&` and `\\` are a literal `&` and
+/// `\`.
+fn template(replacement: &str) -> String {
+    let mut template = String::new();
+    let mut chars = replacement.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(n @ '0'..='9') => template.push_str(&format!("${{{n}}}")),
+                Some('r' | 'n') => template.push('\n'),
+                Some('$') => template.push_str("$$"),
+                Some(c) => template.push(c),
+                None => template.push('\\'),
+            },
+            '&' => template.push_str("${0}"),
+            '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                let mut n = String::new();
+                while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                    n.push(d);
+                }
+
+                template.push_str(&format!("${{{n}}}"));
+            },
+            c => template.push(c),
+        }
+    }
+
+    template
+}
+
+/// A substitution going through the lines of its range, replacing the matches of its pattern.
+pub struct Substitution {
+    regex: Regex,
+    template: String,
+    /// The replacement as typed, shown while confirming each match.
+    replacement: String,
+    global: bool,
+    /// The next line to look for matches in, and the last line of the range.
+    line: usize,
+    last: usize,
+    /// The matches left in the lines already looked into, as byte ranges of the buffer with the
+    /// text replacing each.
+    matches: VecDeque<(Range<usize>, String)>,
+    /// The match waiting for confirmation.
+    current: Option<(Range<usize>, String)>,
+    /// The line of the last match replaced.
+    replaced: Option<usize>,
+}
+
+impl Substitution {
+    /// Returns the next match to replace, with the text replacing it.
+    fn next(&mut self, buffer: &Buffer) -> Option<(Range<usize>, String)> {
+        while self.matches.is_empty() && self.line <= self.last {
+            let line = buffer.line(self.line);
+            let start = buffer.line_to_byte(self.line);
+            let limit = if self.global { usize::MAX } else { 1 };
+
+            for captures in self.regex.captures_iter(&line).take(limit) {
+                let whole = captures.get(0).map_or(0..0, |m| m.range());
+                let mut text = String::new();
+                captures.expand(&self.template, &mut text);
+
+                self.matches.push_back((start + whole.start..start + whole.end, text));
+            }
+
+            self.line += 1;
+        }
+
+        self.matches.pop_front()
+    }
+
+    /// Accounts for `range` having been replaced with `text`, which moves the text after it.
+    fn replaced(&mut self, range: &Range<usize>, text: &str, buffer: &Buffer) {
+        for (next, _) in &mut self.matches {
+            *next = next.start + text.len() - range.len()..next.end + text.len() - range.len();
+        }
+
+        let lines = text.matches('\n').count();
+        self.line += lines;
+        self.last += lines;
+        self.replaced = Some(buffer.byte_to_line(range.start));
+    }
+}
+
+impl Editor {
+    /// Prepares `substitute` to run over the lines `start` to `end`, searching for `pattern`.
+    fn substitution(&self, pattern: &str, substitute: Substitute, start: usize, end: usize) -> Result<Substitution, String> {
+        let regex = Pattern::with_case(pattern, substitute.ignore_case)
+            .ok_or_else(|| format!("Invalid pattern: {pattern}"))?
+            .regex()
+            .clone();
+
+        let replacement = substitute.replacement.unwrap_or_default();
+        Ok(Substitution {
+            regex,
+            template: template(&replacement),
+            replacement,
+            global: substitute.global,
+            line: start,
+            last: end,
+            matches: VecDeque::new(),
+            current: None,
+            replaced: None,
+        })
+    }
+
+    /// Returns the pattern `substitute` searches for, which is the last search when it is empty.
+    fn substitute_pattern(&self, substitute: &Substitute) -> Result<String, String> {
+        match substitute.pattern.as_str() {
+            "" => self.last_search.as_ref().map(|search| search.pattern.clone()).ok_or("No previous pattern".to_string()),
+            pattern => Ok(pattern.to_string()),
+        }
+    }
+
+    /// `:[range]s/pattern/replacement/flags`, replaces the matches of `pattern` in the lines
+    /// `start` to `end`, or asks before replacing each one with the `c` flag.
+    pub(super) fn substitute(&mut self, start: usize, end: usize, args: &str) -> Result<(), String> {
+        let substitute = Substitute::parse(args)?;
+        let confirm = substitute.confirm;
+
+        // The pattern becomes the last search, so `n` goes to its next match afterwards
+        let pattern = self.substitute_pattern(&substitute)?;
+        let forward = self.last_search.as_ref().is_none_or(|search| search.forward);
+        self.set_search(pattern.clone(), forward);
+
+        let mut substitution = self.substitution(&pattern, substitute, start, end)?;
+
+        if confirm {
+            // Commands like `:g` go on without waiting for the answers
+            if self.batch > 0 {
+                return Err("Cannot confirm a substitution from another command".to_string());
+            }
+
+            self.substitution = Some(substitution);
+            self.set_mode(Mode::Confirm);
+            self.next_confirm();
+
+            return match self.mode {
+                Mode::Confirm => Ok(()),
+                _ => Err(format!("Pattern not found: {pattern}")),
+            };
+        }
+
+        while let Some((range, text)) = substitution.next(&self.buffer) {
+            self.buffer_replace(range.clone(), &text);
+            substitution.replaced(&range, &text, &self.buffer);
+        }
+
+        match substitution.replaced {
+            Some(line) => {
+                self.cy = line;
+                self.cx = self.first_non_blank(line);
+                Ok(())
+            },
+            None => Err(format!("Pattern not found: {pattern}")),
+        }
+    }
+
+    /// Shows in the buffer what the substitution typed as `args` would do, without recording it
+    /// in the history. Until the replacement is typed, the matches of the pattern are highlighted
+    /// instead.
+    pub(super) fn preview_substitution(&mut self, start: usize, end: usize, args: &str) {
+        let Ok(substitute) = Substitute::parse(args) else {
+            return;
+        };
+
+        if substitute.replacement.is_none() {
+            if !substitute.pattern.is_empty() {
+                self.highlight = Pattern::with_case(&substitute.pattern, substitute.ignore_case);
+            }
+
+            return;
+        }
+
+        let substitution = self.substitute_pattern(&substitute)
+            .and_then(|pattern| self.substitution(&pattern, substitute, start, end));
+
+        let Ok(mut substitution) = substitution else {
+            return;
+        };
+
+        while let Some((range, text)) = substitution.next(&self.buffer) {
+            let removed = self.buffer.remove(range.clone());
+            self.buffer.insert(range.start, &text);
+
+            self.preview.push(Edit::Remove { pos: range.start, text: removed });
+            self.preview.push(Edit::Insert { pos: range.start, text: text.clone() });
+
+            substitution.replaced(&range, &text, &self.buffer);
+        }
+    }
+
+    /// Reverts the changes shown by [`Editor::preview_substitution`].
+    pub(super) fn revert_preview(&mut self) {
+        for edit in self.preview.drain(..).rev() {
+            edit.revert(&mut self.buffer);
+        }
+    }
+
+    /// Returns the question asked while confirming each match of a substitution.
+    pub fn confirm_message(&self) -> Option<String> {
+        let substitution = self.substitution.as_ref()?;
+        Some(format!("replace with {} (y/n/a/q/l)?", substitution.replacement))
+    }
+
+    /// Answers whether to replace the match under the cursor of a substitution with the `c` flag:
+    /// `y` replaces it, `n` skips it, `a` replaces it and every match after it, `l` replaces it
+    /// and stops, and `q` stops.
+    pub(super) fn confirm_mode(&mut self, key: Key) {
+        match key {
+            Key::Char('y') => {
+                self.replace_current();
+                self.next_confirm();
+            },
+            Key::Char('n') => {
+                self.next_confirm();
+            },
+            Key::Char('a') => {
+                while matches!(self.mode, Mode::Confirm) {
+                    self.replace_current();
+                    self.next_confirm();
+                }
+            },
+            Key::Char('l') => {
+                self.replace_current();
+                self.finish_confirm();
+            },
+            Key::Char('q') | Key::Esc => {
+                self.finish_confirm();
+            },
+            _ => { },
+        }
+    }
+
+    fn replace_current(&mut self) {
+        let Some((range, text)) = self.substitution.as_mut().and_then(|substitution| substitution.current.take()) else {
+            return;
+        };
+
+        self.buffer_replace(range.clone(), &text);
+
+        if let Some(substitution) = &mut self.substitution {
+            substitution.replaced(&range, &text, &self.buffer);
+        }
+    }
+
+    /// Moves the cursor to the next match to confirm, or ends the substitution after the last one.
+    fn next_confirm(&mut self) {
+        let Some(substitution) = &mut self.substitution else {
+            return;
+        };
+
+        substitution.current = substitution.next(&self.buffer);
+
+        match substitution.current.as_ref().map(|(range, _)| range.start) {
+            Some(pos) => {
+                (self.cx, self.cy) = self.buffer.byte_to_cursor(pos);
+            },
+            None => self.finish_confirm(),
+        }
+    }
+
+    fn finish_confirm(&mut self) {
+        if let Some(line) = self.substitution.take().and_then(|substitution| substitution.replaced) {
+            self.cy = line;
+            self.cx = self.first_non_blank(line);
+        }
+
+        self.set_mode(Mode::Normal);
+        self.clamp_normal();
+        self.cx_keep = self.cx;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replaces the first match of `pattern` in `text` with `replacement`, as typed after `:s`.
+    fn replace(pattern: &str, text: &str, replacement: &str) -> String {
+        let regex = Regex::new(pattern).unwrap();
+        let captures = regex.captures(text).unwrap();
+
+        let mut expanded = String::new();
+        captures.expand(&template(replacement), &mut expanded);
+        expanded
+    }
+
+    #[test]
+    fn replacements_expand_captures() {
+        assert_eq!(replace(r"(\w+) (\w+)", "foo bar", r"\2 \1"), "bar foo");
+        assert_eq!(replace(r"(\w+) (\w+)", "foo bar", "$2-$1"), "bar-foo");
+        assert_eq!(replace(r"o+", "foo", "<&>"), "<oo>");
+        assert_eq!(replace(r"o+", "foo", "[$0]"), "[oo]");
+        assert_eq!(replace(r"(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)", "abcdefghij", "$10"), "j");
+    }
+
+    #[test]
+    fn replacements_escape_special_characters() {
+        assert_eq!(replace("a", "a", r"\&"), "&");
+        assert_eq!(replace("a", "a", r"\\"), "\\");
+        assert_eq!(replace("a", "a", r"\$1"), "$1");
+        assert_eq!(replace("a", "a", "$"), "$");
+        assert_eq!(replace("a", "a", r"x\ry\nz"), "x\ny\nz");
+    }
+
+    #[test]
+    fn delimiters_and_flags() {
+        let substitute = Substitute::parse("#a/b#c\\#d#gI").unwrap();
+        assert_eq!(substitute.pattern, "a/b");
+        assert_eq!(substitute.replacement.as_deref(), Some("c#d"));
+        assert!(substitute.global && !substitute.confirm);
+        assert_eq!(substitute.ignore_case, Some(false));
+
+        assert_eq!(Substitute::parse("/a").unwrap().replacement, None);
+        assert!(Substitute::parse("xaxbx").is_err());
+        assert!(Substitute::parse("/a/b/z").is_err());
+    }
+}
@@ -0,0 +1,511 @@
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{Editor, movement::{CharClass, Scanner}};
+
+/// A text object, which selects the text around the cursor, like `iw` or `a(`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TextObject {
+    pub kind: ObjectKind,
+    /// Whether the object is typed with `a`, taking the blanks or the delimiters around the text
+    /// too, instead of `i`.
+    pub around: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ObjectKind {
+    /// `w` and `W`
+    Word { big: bool },
+    /// `s`
+    Sentence,
+    /// `p`
+    Paragraph,
+    /// `(`, `{`, `[` and `<`, which can be nested, along with their closing characters.
+    Pair(char, char),
+    /// `"`, `'` and `` ` ``, which only go as far as the end of the line.
+    Quote(char),
+    /// `t`, a pair of XML or HTML tags.
+    Tag,
+}
+
+impl Editor {
+    /// Returns the byte range of the text that `object` covers around the cursor, repeating it
+    /// `count` times, and whether the range covers whole lines.
+    pub fn object_range(&self, object: TextObject, count: Option<usize>) -> Option<(Range<usize>, bool)> {
+        let n = count.unwrap_or(1);
+
+        match object.kind {
+            ObjectKind::Word { big } => self.word_object(n, big, object.around).map(|range| (range, false)),
+            ObjectKind::Sentence => Some((self.sentence_object(n, object.around), false)),
+            ObjectKind::Paragraph => Some((self.paragraph_object(n, object.around), true)),
+            ObjectKind::Pair(open, close) => self.pair_object(open, close, n, object.around),
+            ObjectKind::Quote(quote) => self.quote_object(quote, object.around).map(|range| (range, false)),
+            ObjectKind::Tag => self.tag_object(n, object.around).map(|range| (range, false)),
+        }
+    }
+
+    /// Words and the blanks between them each count as one for `iw`, while `aw` takes a word
+    /// along with the blanks after it, or before it when there are none after it.
+    fn word_object(&self, n: usize, big: bool, around: bool) -> Option<Range<usize>> {
+        let line: Vec<CharClass> = self.buffer
+            .line(self.cy)
+            .graphemes(true)
+            .map(|g| CharClass::of(g.chars().next().unwrap_or(' '), big))
+            .collect();
+
+        if line.is_empty() {
+            return None;
+        }
+
+        // The end of the run of characters of the same class that starts at `x`.
+        let run_end = |x: usize| {
+            let mut end = x + 1;
+            while end < line.len() && line[end] == line[x] {
+                end += 1;
+            }
+            end
+        };
+
+        let cx = self.cx.min(line.len() - 1);
+        let mut start = cx;
+        while start > 0 && line[start - 1] == line[cx] {
+            start -= 1;
+        }
+
+        let mut end = start;
+        let mut trailing = false;
+
+        for _ in 0..n {
+            if end >= line.len() {
+                break;
+            }
+
+            let blank = line[end] == CharClass::Blank;
+            end = run_end(end);
+            trailing = false;
+
+            if around && end < line.len() && (blank || line[end] == CharClass::Blank) {
+                end = run_end(end);
+                trailing = !blank;
+            }
+        }
+
+        if around && line[cx] != CharClass::Blank && !trailing {
+            while start > 0 && line[start - 1] == CharClass::Blank {
+                start -= 1;
+            }
+        }
+
+        Some(self.buffer.grapheme_to_byte(self.cy, start)..self.buffer.grapheme_to_byte(self.cy, end))
+    }
+
+    /// `is` stops before the blanks after the last sentence, which `as` takes too.
+    fn sentence_object(&self, n: usize, around: bool) -> Range<usize> {
+        let mut scanner = Scanner::new(self, self.cx, self.cy, false);
+        if !scanner.at_sentence_start() {
+            scanner.prev_sentence();
+        }
+
+        let (sx, sy) = scanner.pos();
+        let start = self.buffer.grapheme_to_byte(sy, sx);
+
+        for _ in 0..n {
+            if !scanner.next_sentence() {
+                break;
+            }
+        }
+
+        let (ex, ey) = scanner.pos();
+        let end = self.buffer.grapheme_to_byte(ey, ex);
+
+        // The line breaks before the empty line ending a paragraph are never part of it.
+        if around && !scanner.at_empty_line() {
+            return start..end;
+        }
+
+        start..start + self.buffer.slice(start..end).trim_end().len()
+    }
+
+    /// Paragraphs and the empty lines between them each count as one for `ip`, while `ap` takes
+    /// a paragraph along with the empty lines after it, or before it when there are none after it.
+    fn paragraph_object(&self, n: usize, around: bool) -> Range<usize> {
+        let last = self.content_lines();
+        let empty = |y: usize| self.buffer.line_width(y) == 0;
+
+        // The last line of the run of empty or non-empty lines that starts at `y`.
+        let run_end = |y: usize| {
+            let mut end = y;
+            while end < last && empty(end + 1) == empty(y) {
+                end += 1;
+            }
+            end
+        };
+
+        let mut first = self.cy;
+        while first > 0 && empty(first - 1) == empty(self.cy) {
+            first -= 1;
+        }
+
+        let mut end = first;
+        let mut next = first;
+        let mut trailing = false;
+
+        for _ in 0..n {
+            if next > last {
+                break;
+            }
+
+            let blank = empty(next);
+            end = run_end(next);
+            trailing = false;
+
+            if around && end < last {
+                end = run_end(end + 1);
+                trailing = !blank;
+            }
+
+            next = end + 1;
+        }
+
+        if around && !empty(self.cy) && !trailing {
+            while first > 0 && empty(first - 1) {
+                first -= 1;
+            }
+        }
+
+        self.lines_range(first, end)
+    }
+
+    /// The `count`th pair of `open` and `close` around the cursor, counting nested pairs. When
+    /// the delimiters are on lines of their own, `i(` covers the lines between them as a whole.
+    fn pair_object(&self, open: char, close: char, n: usize, around: bool) -> Option<(Range<usize>, bool)> {
+        let pos = self.cursor_pos();
+
+        let mut open_pos = if self.buffer.char_at(pos) == Some(open) {
+            pos
+        } else {
+            self.find_open(open, close, pos)?
+        };
+
+        for _ in 1..n {
+            open_pos = self.find_open(open, close, open_pos)?;
+        }
+
+        let mut depth = 0;
+        let close_pos = self.buffer
+            .chars_from(open_pos + open.len_utf8())
+            .find(|&(_, c)| {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 {
+                        return true;
+                    }
+                    depth -= 1;
+                }
+                false
+            })?
+            .0;
+
+        if around {
+            return Some((open_pos..close_pos + close.len_utf8(), false));
+        }
+
+        let start = open_pos + open.len_utf8();
+        if self.buffer.char_at(start) == Some('\n') {
+            let close_line = self.buffer.line_to_byte(self.buffer.byte_to_line(close_pos));
+
+            if self.buffer.slice(close_line..close_pos).trim().is_empty() {
+                return if start + 1 < close_line {
+                    Some((start + 1..close_line, true))
+                } else {
+                    Some((start + 1..start + 1, false))
+                };
+            }
+        }
+
+        Some((start..close_pos, false))
+    }
+
+    /// Returns the byte offset of the `open` before `pos` that is not closed by a `close` before
+    /// `pos`.
+    fn find_open(&self, open: char, close: char, pos: usize) -> Option<usize> {
+        let mut depth = 0;
+
+        for (p, c) in self.buffer.chars_before(pos) {
+            if c == close {
+                depth += 1;
+            } else if c == open {
+                if depth == 0 {
+                    return Some(p);
+                }
+                depth -= 1;
+            }
+        }
+
+        None
+    }
+
+    /// The quoted text the cursor is in, or the first one after the cursor in the line. Quotes
+    /// escaped with `\` are skipped, and `a"` takes the blanks after the closing quote too, or
+    /// before the opening one when there are none after it.
+    fn quote_object(&self, quote: char, around: bool) -> Option<Range<usize>> {
+        let line = self.buffer.line(self.cy);
+        let offset = self.buffer.line_to_byte(self.cy);
+        let cursor = self.cursor_pos() - offset;
+
+        let mut quotes = vec![];
+        let mut escaped = false;
+
+        for (i, c) in line.char_indices() {
+            if c == quote && !escaped {
+                quotes.push(i);
+            }
+
+            escaped = c == '\\' && !escaped;
+        }
+
+        let (open, close) = quotes
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|&(_, close)| cursor <= close)?;
+
+        if !around {
+            return Some(offset + open + quote.len_utf8()..offset + close);
+        }
+
+        let is_blank = |c: char| c == ' ' || c == '\t';
+
+        let mut start = open;
+        let mut end = close + quote.len_utf8();
+
+        let trailing = line[end..].len() - line[end..].trim_start_matches(is_blank).len();
+        if trailing > 0 {
+            end += trailing;
+        } else {
+            start = line[..start].trim_end_matches(is_blank).len();
+        }
+
+        Some(offset + start..offset + end)
+    }
+
+    /// The `count`th pair of tags around the cursor, like `<b>` and `</b>`. `it` covers the text
+    /// between the tags, and `at` the tags too.
+    ///
+    /// The text is only read outwards from the cursor, going left for the opening tags not closed
+    /// before it and right for the closing tags, as far as the pair asked for.
+    fn tag_object(&self, n: usize, around: bool) -> Option<Range<usize>> {
+        let pos = self.cursor_pos();
+
+        // A tag under the cursor is the first one found on its side
+        let (left, right) = match self.tag_under(pos) {
+            Some(tag) if tag.closing => (tag.range.start, tag.range.start),
+            Some(tag) => (tag.range.end, tag.range.end),
+            None => (pos, pos),
+        };
+
+        // The opening tags left of the cursor not closed before it, from the innermost one.
+        let mut closed: Vec<String> = vec![];
+        let mut opening = self.tags(self.buffer.chars_before(left)).filter(move |tag| {
+            if tag.closing {
+                closed.push(tag.name.clone());
+                return false;
+            }
+
+            match closed.iter().rposition(|name| *name == tag.name) {
+                Some(k) => {
+                    closed.remove(k);
+                    false
+                },
+                None => true,
+            }
+        });
+
+        // The tags opened right of the cursor, and the ones left of it still waiting to be closed.
+        let mut open: Vec<String> = vec![];
+        let mut enclosing: Vec<Tag> = vec![];
+        let mut found = 0;
+
+        for tag in self.tags(self.buffer.chars_from(right)) {
+            if !tag.closing {
+                open.push(tag.name);
+                continue;
+            }
+
+            if let Some(k) = open.iter().rposition(|name| *name == tag.name) {
+                open.truncate(k);
+                continue;
+            }
+
+            // Going through the opening tags as far as one this tag closes, leaving the ones
+            // inside it unclosed.
+            let k = loop {
+                if let Some(k) = enclosing.iter().position(|open| open.name == tag.name) {
+                    break Some(k);
+                }
+
+                match opening.next() {
+                    Some(open) => enclosing.push(open),
+                    None => break None,
+                }
+            };
+
+            let Some(open) = k.and_then(|k| enclosing.drain(..=k).next_back()) else {
+                continue;
+            };
+
+            found += 1;
+            if found == n {
+                return Some(if around { open.range.start..tag.range.end } else { open.range.end..tag.range.start });
+            }
+        }
+
+        None
+    }
+
+    /// Returns the tags starting at the `<` among `chars`, in the order they come.
+    fn tags<'a>(&'a self, chars: impl Iterator<Item = (usize, char)> + 'a) -> impl Iterator<Item = Tag> + 'a {
+        chars.filter(|(_, c)| *c == '<').filter_map(|(start, _)| self.tag_at(start))
+    }
+
+    /// Returns the tag starting at the `<` at the byte offset `start`. Comments, declarations and
+    /// tags closing themselves, like `<br/>`, are left out.
+    fn tag_at(&self, start: usize) -> Option<Tag> {
+        let mut text = String::new();
+        let mut end = None;
+
+        for (i, c) in self.buffer.chars_from(start).skip(1) {
+            match c {
+                '>' => {
+                    end = Some(i + 1);
+                    break;
+                },
+                '<' => return None,
+                c => text.push(c),
+            }
+        }
+
+        let range = start..end?;
+
+        if let Some(name) = text.strip_prefix('/') {
+            return Some(Tag { name: name.trim().to_string(), closing: true, range });
+        }
+
+        if text.ends_with('/') || text.starts_with(['!', '?']) {
+            return None;
+        }
+
+        let name = text.split_whitespace().next()?;
+        Some(Tag { name: name.to_string(), closing: false, range })
+    }
+
+    /// Returns the tag the byte offset `pos` is in.
+    fn tag_under(&self, pos: usize) -> Option<Tag> {
+        let start = self.buffer
+            .chars_from(pos)
+            .take(1)
+            .chain(self.buffer.chars_before(pos))
+            .find(|(i, c)| *c == '<' || (*c == '>' && *i < pos))
+            .filter(|(_, c)| *c == '<')?
+            .0;
+
+        self.tag_at(start).filter(|tag| tag.range.contains(&pos))
+    }
+}
+
+/// A tag of the text, like `<b>` or `</b>`.
+struct Tag {
+    name: String,
+    closing: bool,
+    range: Range<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{command::{self, Action, Parse, Target}, key::Key};
+    use super::*;
+
+    /// Returns the text an object typed as `keys`, like `2i(`, covers from `cursor` in `text`,
+    /// and whether it covers whole lines.
+    fn covered(text: &str, cursor: (usize, usize), keys: &str) -> Option<(String, bool)> {
+        let mut editor = Editor::new(text);
+        (editor.cx, editor.cy) = cursor;
+
+        let Parse::Done(cmd) = command::parse(&Key::parse(&format!("y{keys}"))) else {
+            panic!("not an object: {keys}");
+        };
+        let Action::Operate(_, Target::Object(object)) = cmd.action else {
+            panic!("not an object: {keys}");
+        };
+
+        let (range, linewise) = editor.object_range(object, cmd.count)?;
+        Some((editor.buffer.slice(range).to_string(), linewise))
+    }
+
+    fn chars(text: &str) -> Option<(String, bool)> {
+        Some((text.to_string(), false))
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(covered("foo bar baz", (5, 0), "iw"), chars("bar"));
+        assert_eq!(covered("foo bar baz", (5, 0), "aw"), chars("bar "));
+        assert_eq!(covered("foo bar baz", (5, 0), "2iw"), chars("bar "));
+        assert_eq!(covered("foo bar baz", (9, 0), "aw"), chars(" baz"));
+        assert_eq!(covered("foo  bar", (3, 0), "iw"), chars("  "));
+        assert_eq!(covered("a.b c", (0, 0), "iW"), chars("a.b"));
+        assert_eq!(covered("a.b c", (0, 0), "iw"), chars("a"));
+        assert_eq!(covered("ae\u{301}b c", (1, 0), "iw"), chars("ae\u{301}b"));
+    }
+
+    #[test]
+    fn sentences() {
+        assert_eq!(covered("One. Two three. Four", (6, 0), "is"), chars("Two three."));
+        assert_eq!(covered("One. Two three. Four", (6, 0), "as"), chars("Two three. "));
+        assert_eq!(covered("One. Two three. Four", (0, 0), "2is"), chars("One. Two three."));
+        assert_eq!(covered("One.\n\nTwo.", (1, 0), "as"), chars("One."));
+    }
+
+    #[test]
+    fn paragraphs() {
+        assert_eq!(covered("a\nb\n\nc", (0, 0), "ip"), Some(("a\nb\n".to_string(), true)));
+        assert_eq!(covered("a\nb\n\nc", (0, 0), "ap"), Some(("a\nb\n\n".to_string(), true)));
+        assert_eq!(covered("a\nb\n\nc", (0, 0), "2ip"), Some(("a\nb\n\n".to_string(), true)));
+    }
+
+    #[test]
+    fn pairs() {
+        assert_eq!(covered("f(a, (b))", (6, 0), "i("), chars("b"));
+        assert_eq!(covered("f(a, (b))", (6, 0), "a("), chars("(b)"));
+        assert_eq!(covered("f(a, (b))", (6, 0), "2i("), chars("a, (b)"));
+        assert_eq!(covered("f(a, (b))", (1, 0), "ib"), chars("a, (b)"));
+        assert_eq!(covered("[x [y]]", (4, 0), "2i["), chars("x [y]"));
+        assert_eq!(covered("[x [y]]", (4, 0), "3i["), None);
+        assert_eq!(covered("f(a)", (0, 0), "i("), None);
+
+        // With the braces on lines of their own, the lines between them are taken whole
+        assert_eq!(covered("if {\n  a\n}", (2, 1), "i{"), Some(("  a\n".to_string(), true)));
+        assert_eq!(covered("if {\n  a\n}", (2, 1), "a{"), chars("{\n  a\n}"));
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(covered("say \"hi\" now", (5, 0), "i\""), chars("hi"));
+        assert_eq!(covered("say \"hi\" now", (5, 0), "a\""), chars("\"hi\" "));
+        assert_eq!(covered("x `y` z", (3, 0), "a`"), chars("`y` "));
+        assert_eq!(covered("x `y`", (3, 0), "a`"), chars(" `y`"));
+        assert_eq!(covered("no quotes", (0, 0), "i'"), None);
+    }
+
+    #[test]
+    fn tags() {
+        let text = "<a><b>x</b><br/></a>";
+        assert_eq!(covered(text, (6, 0), "it"), chars("x"));
+        assert_eq!(covered(text, (6, 0), "at"), chars("<b>x</b>"));
+        assert_eq!(covered(text, (6, 0), "2it"), chars("<b>x</b><br/>"));
+        assert_eq!(covered(text, (6, 0), "2at"), chars(text));
+        assert_eq!(covered(text, (8, 0), "it"), chars("x"));
+        assert_eq!(covered(text, (6, 0), "3it"), None);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::util::usize_sub;
+
+use super::{Editor, movement::Motion};
+
+/// How `Ctrl-E`, `Ctrl-Y`, `Ctrl-D`, `Ctrl-U`, `Ctrl-F` and `Ctrl-B` scroll the text.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scroll {
+    /// `Ctrl-E` and `Ctrl-Y`, by as many lines as the count, leaving the cursor on its line while
+    /// it stays on the screen.
+    Lines { down: bool },
+    /// `Ctrl-D` and `Ctrl-U`, by half a screen, or by as many lines as the count, taking the
+    /// cursor along.
+    Half { down: bool },
+    /// `Ctrl-F` and `Ctrl-B`, by a screen for each count, keeping two lines of the last one.
+    Page { down: bool },
+}
+
+/// Where `zt`, `zz` and `zb` put the line of the cursor on the screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Align {
+    Top,
+    Center,
+    Bottom,
+}
+
+impl Editor {
+    /// Sets how many lines and columns of text fit on the screen.
+    pub fn resize(&mut self, rows: usize, columns: usize) {
+        self.rows = rows;
+        self.columns = columns;
+        self.scroll_to_cursor();
+    }
+
+    /// Returns how many lines and columns of text fit on the screen.
+    pub fn size(&self) -> (usize, usize) {
+        (self.rows, self.columns)
+    }
+
+    /// Returns the lines on the screen, from the top one, with the last one only partly on it
+    /// when it wraps past the bottom.
+    pub fn visible_lines(&self) -> std::ops::Range<usize> {
+        let mut end = self.top;
+        let mut rows = 0;
+
+        while end <= self.content_lines() && rows < self.rows.max(1) {
+            rows += self.line_rows(end).len();
+            end += 1;
+        }
+
+        self.top..end.max(self.top + 1)
+    }
+
+    /// Returns the top line of the screen showing the line `n` at its bottom, which is `n` itself
+    /// when it wraps over more rows than the screen has.
+    fn top_showing(&self, n: usize) -> usize {
+        let mut top = n;
+        let mut rows = self.line_rows(n).len();
+
+        while top > 0 {
+            rows += self.line_rows(top - 1).len();
+            if rows > self.rows {
+                break;
+            }
+
+            top -= 1;
+        }
+
+        top
+    }
+
+    /// Returns how many columns the gutter takes on the left of the text, which fits the number
+    /// of the last line and a blank after it, or `0` when it shows no numbers.
+    pub fn gutter_width(&self) -> usize {
+        if !self.options.number && !self.options.relativenumber {
+            return 0;
+        }
+
+        self.buffer.len_lines().to_string().len().max(3) + 1
+    }
+
+    /// Returns the number shown in the gutter for the line `n`, padded to the width of the gutter.
+    /// With both 'number' and 'relativenumber', the line of the cursor shows its own number on the
+    /// left, while the others show how far they are from it.
+    pub fn line_number(&self, n: usize) -> String {
+        let width = usize_sub(self.gutter_width(), 1);
+
+        match (self.options.number, self.options.relativenumber) {
+            (true, true) if n == self.cy => format!("{:<width$}", n + 1),
+            (true, false) => format!("{:>width$}", n + 1),
+            _ => format!("{:>width$}", n.abs_diff(self.cy)),
+        }
+    }
+
+    /// Returns how many lines are kept between the cursor and the top and bottom of the screen,
+    /// which is less than 'scrolloff' when the screen is too short for it.
+    fn scrolloff(&self) -> usize {
+        self.options.scrolloff.min(usize_sub(self.rows, 1) / 2)
+    }
+
+    /// Scrolls the text so the cursor is on the screen, with 'scrolloff' lines around it.
+    pub(super) fn scroll_to_cursor(&mut self) {
+        if self.rows == 0 {
+            return;
+        }
+
+        let off = self.scrolloff();
+        if self.cy < self.top + off {
+            self.top = usize_sub(self.cy, off);
+        } else {
+            // Near the end, the lines kept below the cursor stop at the last one
+            let below = (self.cy + off).min(self.content_lines());
+            self.top = self.top.max(self.top_showing(below).min(self.cy));
+        }
+        self.top = self.top.min(self.content_lines());
+
+        // Wrapped lines never go past the right edge
+        if self.options.wrap {
+            self.left = 0;
+            return;
+        }
+
+        let side = self.options.sidescrolloff.min(usize_sub(self.columns, 1) / 2);
+        if self.cx < self.left + side {
+            self.left = usize_sub(self.cx, side);
+        } else if self.cx + side >= self.left + self.columns {
+            self.left = self.cx + side + 1 - self.columns;
+        }
+    }
+
+    /// Scrolls the text as `scroll` does, `count` times or by `count` lines.
+    pub(super) fn scroll(&mut self, scroll: Scroll, count: Option<usize>) {
+        let n = count.unwrap_or(1);
+        let last = self.content_lines();
+
+        match scroll {
+            Scroll::Lines { down: true } => {
+                self.top = (self.top + n).min(last);
+            },
+            Scroll::Lines { down: false } => {
+                self.top = usize_sub(self.top, n);
+            },
+            Scroll::Half { down } => {
+                // A count sets how far the next ones scroll too
+                if let Some(count) = count {
+                    self.scroll_lines = count;
+                }
+
+                let n = match self.scroll_lines {
+                    0 => (self.rows / 2).max(1),
+                    n => n,
+                };
+
+                if down {
+                    if self.cy == last {
+                        return;
+                    }
+
+                    self.top = (self.top + n).min(usize_sub(last + 1, self.rows).max(self.top));
+                    self.cy = (self.cy + n).min(last);
+                } else {
+                    if self.cy == 0 {
+                        return;
+                    }
+
+                    self.top = usize_sub(self.top, n);
+                    self.cy = usize_sub(self.cy, n);
+                }
+            },
+            Scroll::Page { down } => {
+                let page = usize_sub(self.rows, 2).max(1) * n;
+
+                if down {
+                    self.top = (self.top + page).min(last);
+                } else {
+                    self.top = usize_sub(self.top, page);
+                }
+            },
+        }
+
+        self.cursor_into_view();
+        self.cx = self.cx_keep.min(usize_sub(self.buffer.line_width(self.cy), 1));
+    }
+
+    /// Moves the cursor into the lines on the screen, 'scrolloff' lines away from its edges, when
+    /// it is out of them.
+    pub(super) fn cursor_into_view(&mut self) {
+        let last = self.content_lines();
+        let off = self.scrolloff();
+        let first = if self.top == 0 { 0 } else { self.top + off };
+        let bottom = self.visible_lines().end - 1;
+        let end = if bottom >= last { last } else { usize_sub(bottom, off) };
+
+        let cy = self.cy.clamp(first.min(end), end).min(last);
+        if cy != self.cy {
+            self.cy = cy;
+            self.cx = self.cx_keep.min(usize_sub(self.buffer.line_width(cy), 1));
+        }
+    }
+
+    /// `zt`, `zz` and `zb`, scroll the text so the line of the cursor is at the top, in the middle
+    /// or at the bottom of the screen.
+    pub(super) fn align(&mut self, align: Align) {
+        let off = self.scrolloff();
+
+        self.top = match align {
+            Align::Top => usize_sub(self.cy, off),
+            Align::Center => usize_sub(self.cy, usize_sub(self.rows, 1) / 2),
+            Align::Bottom => self.top_showing((self.cy + off).min(self.content_lines())).min(self.cy),
+        };
+    }
+
+    /// Returns the line `H`, `M` or `L` moves to: the line `n` from the top or from the bottom of
+    /// the screen, keeping 'scrolloff' lines away from its edges, or the line in its middle.
+    pub(super) fn screen_line(&self, motion: Motion, n: usize) -> usize {
+        let last = self.content_lines();
+        let bottom = (self.visible_lines().end - 1).min(last);
+        let off = self.scrolloff();
+
+        match motion {
+            Motion::ScreenTop => {
+                let line = (self.top + n - 1).min(bottom);
+                if self.top > 0 { line.max(self.top + off).min(bottom) } else { line }
+            },
+            Motion::ScreenBottom => {
+                let line = usize_sub(bottom, n - 1).max(self.top);
+                if bottom < last { line.min(usize_sub(bottom, off)).max(self.top) } else { line }
+            },
+            _ => (self.top + bottom) / 2,
+        }
+    }
+}
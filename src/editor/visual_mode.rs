@@ -1,18 +1,366 @@
-use super::{Editor, Mode};
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{Editor, Mode, command::{self, Parse}, key::Key, operator::Operator, register::RegisterKind, textobject::TextObject};
+
+/// How a visual selection covers the text between its anchor and the cursor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VisualKind {
+    /// `v`, every character in between.
+    Char,
+    /// `V`, every line in between.
+    Line,
+    /// `Ctrl-V`, the columns in between on every line in between.
+    Block,
+}
+
+/// A visual selection, which goes from `anchor` to the cursor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Visual {
+    pub kind: VisualKind,
+    /// Where the selection started, as `(cx, cy)`.
+    pub anchor: (usize, usize),
+}
+
+/// A change made with `c` over a block, whose text typed in the first line gets copied to the
+/// other lines when leaving insert mode.
+pub struct BlockInsert {
+    first: usize,
+    last: usize,
+    col: usize,
+}
 
 impl Editor {
-    pub(super) fn visual_mode(&mut self, event: sdl2::event::Event) {
-        match event {
-            sdl2::event::Event::KeyDown { keycode, .. } => {
-                match keycode {
-                    // Quit visual mode
-                    Some(sdl2::keyboard::Keycode::Escape) => {
-                        self.mode = Mode::Normal
-                    },
-                    _ => { },
-                }
-            },
-            _ => { },
+    pub(super) fn visual_mode(&mut self, key: Key) {
+        // Cancel the command being typed, or quit visual mode
+        if key == Key::Esc {
+            if self.cmd_stack.is_empty() {
+                self.exit_visual();
+            }
+
+            self.cmd_stack.clear();
+            return;
+        }
+
+        self.cmd_stack.push(key);
+
+        match command::parse_visual(&self.cmd_stack) {
+            Parse::Pending => { },
+            Parse::Invalid => {
+                self.cmd_stack.clear();
+            },
+            Parse::Done(cmd) => {
+                self.cmd_stack.clear();
+                self.execute(cmd);
+            },
+        }
+    }
+
+    /// Starts a visual selection of `kind` at the cursor. In visual mode, it changes the kind of
+    /// the selection instead, or quits visual mode when it already is of `kind`.
+    pub(super) fn toggle_visual(&mut self, kind: VisualKind) {
+        match self.mode {
+            Mode::Visual(visual) if visual.kind == kind => {
+                self.exit_visual();
+            },
+            Mode::Visual(visual) => {
+                self.set_mode(Mode::Visual(Visual { kind, ..visual }));
+            },
+            _ => {
+                self.set_mode(Mode::Visual(Visual { kind, anchor: (self.cx, self.cy) }));
+            },
+        }
+    }
+
+    /// Quits visual mode, keeping the selection around for `gv`.
+    pub(super) fn exit_visual(&mut self) {
+        if let Mode::Visual(visual) = self.mode {
+            self.last_visual = Some((visual, (self.cx, self.cy)));
+            self.set_mode(Mode::Normal);
+        }
+    }
+
+    /// `gv`, selects the last visual selection again. In visual mode, the current selection
+    /// becomes the last one.
+    pub(super) fn reselect(&mut self) {
+        let Some((visual, (cx, cy))) = self.last_visual else {
+            return;
+        };
+
+        if let Mode::Visual(current) = self.mode {
+            self.last_visual = Some((current, (self.cx, self.cy)));
+        }
+
+        let last = self.content_lines();
+        let anchor = (visual.anchor.0, visual.anchor.1.min(last));
+
+        self.set_mode(Mode::Visual(Visual { anchor, ..visual }));
+        self.cy = cy.min(last);
+        self.cx = cx;
+    }
+
+    /// `o`, moves the cursor to the other end of the selection. With `column` set, like in `O`,
+    /// a block only swaps its columns, so the cursor stays in its line.
+    pub(super) fn swap_ends(&mut self, column: bool) {
+        let Mode::Visual(mut visual) = self.mode else {
+            return;
+        };
+
+        if column && visual.kind == VisualKind::Block {
+            (visual.anchor.0, self.cx) = (self.cx, visual.anchor.0);
+        } else {
+            (visual.anchor, (self.cx, self.cy)) = ((self.cx, self.cy), visual.anchor);
+        }
+
+        self.set_mode(Mode::Visual(visual));
+    }
+
+    /// Selects the text covered by `object`, switching to a linewise selection for the objects
+    /// that cover whole lines, like `ip`.
+    pub(super) fn select_object(&mut self, object: TextObject, count: Option<usize>) {
+        let Some((range, linewise)) = self.object_range(object, count) else {
+            return;
+        };
+
+        if range.is_empty() {
+            return;
+        }
+
+        let anchor = self.buffer.byte_to_cursor(range.start);
+        let (ex, ey) = self.buffer.byte_to_cursor(range.end);
+
+        (self.cx, self.cy) = match (ex, linewise) {
+            (_, true) if ex == 0 && ey > anchor.1 => (0, ey - 1),
+            (_, true) => (0, ey),
+            (0, false) => (self.buffer.line_width(ey - 1), ey - 1),
+            (ex, false) => (ex - 1, ey),
+        };
+
+        let kind = if linewise { VisualKind::Line } else { VisualKind::Char };
+        self.set_mode(Mode::Visual(Visual { kind, anchor }));
+    }
+
+    /// Returns the start and the end of the selection, as `(cx, cy)`, in the order they appear
+    /// in the buffer.
+    pub fn selection_bounds(&self) -> Option<((usize, usize), (usize, usize))> {
+        let Mode::Visual(visual) = self.mode else {
+            return None;
+        };
+
+        let (ax, ay) = visual.anchor;
+        let cursor = (self.cx, self.cy);
+
+        Some(if (ay, ax) <= (self.cy, self.cx) { (visual.anchor, cursor) } else { (cursor, visual.anchor) })
+    }
+
+    /// Returns the byte range the selection covers and whether it covers whole lines, as
+    /// [`Editor::target_range`] does. A block has no single range, so it returns `None` for them
+    /// unless `lines` is set, which makes every selection cover its lines as a whole.
+    pub fn selection_range(&self, lines: bool) -> Option<(Range<usize>, bool)> {
+        let Mode::Visual(visual) = self.mode else {
+            return None;
+        };
+
+        let ((sx, sy), (ex, ey)) = self.selection_bounds()?;
+
+        match visual.kind {
+            _ if lines => Some((self.lines_range(sy, ey), true)),
+            VisualKind::Line => Some((self.lines_range(sy, ey), true)),
+            VisualKind::Block => None,
+            VisualKind::Char => {
+                let start = self.buffer.grapheme_to_byte(sy, sx);
+
+                // The line break is selected too when the end is past the text of its line, like
+                // on an empty line or after "$".
+                let past_end = ex >= self.buffer.line_width(ey) || ((ex, ey) == (self.cx, self.cy) && self.cx_keep == usize::MAX);
+                let end = if past_end && ey < self.content_lines() {
+                    self.buffer.line_to_byte(ey + 1)
+                } else {
+                    self.buffer.grapheme_to_byte(ey, ex + 1)
+                };
+
+                Some((start..end, false))
+            },
         }
     }
+
+    /// Returns the byte range of the selected columns for every line of a block selection, from
+    /// the first line. After `$`, the block goes to the end of every line.
+    pub fn block_ranges(&self) -> Vec<Range<usize>> {
+        let Mode::Visual(visual) = self.mode else {
+            return vec![];
+        };
+
+        let (ax, ay) = visual.anchor;
+        let left = ax.min(self.cx);
+        let right = if self.cx_keep == usize::MAX { usize::MAX } else { ax.max(self.cx) + 1 };
+
+        (ay.min(self.cy)..=ay.max(self.cy))
+            .map(|n| self.buffer.grapheme_to_byte(n, left)..self.buffer.grapheme_to_byte(n, right))
+            .collect()
+    }
+
+    /// Returns the columns of the line `n` covered by the selection, where the column past the end
+    /// of the line stands for its line break. Linewise selections go up to `usize::MAX`, as they
+    /// take the whole line.
+    pub fn selection_columns(&self, n: usize) -> Option<Range<usize>> {
+        let Mode::Visual(visual) = self.mode else {
+            return None;
+        };
+
+        let ((sx, sy), (ex, ey)) = self.selection_bounds()?;
+        if n < sy || n > ey {
+            return None;
+        }
+
+        let width = self.buffer.line_width(n);
+
+        match visual.kind {
+            VisualKind::Line => Some(0..usize::MAX),
+            VisualKind::Block => {
+                let left = visual.anchor.0.min(self.cx);
+                let right = if self.cx_keep == usize::MAX { width } else { visual.anchor.0.max(self.cx) + 1 };
+
+                Some(left..right.max(left))
+            },
+            VisualKind::Char => {
+                let start = if n == sy { sx } else { 0 };
+                let past_end = (ex, ey) == (self.cx, self.cy) && self.cx_keep == usize::MAX;
+                let end = if n == ey && !past_end { ex.min(width) + 1 } else { width + 1 };
+
+                Some(start..end)
+            },
+        }
+    }
+
+    /// Applies `operator` to the selection, or to its lines as a whole when `lines` is set, and
+    /// quits visual mode. Text deleted, changed or yanked goes into the register `register`.
+    pub(super) fn operate_selection(&mut self, operator: Operator, lines: bool, count: usize, register: Option<char>) {
+        let Mode::Visual(visual) = self.mode else {
+            return;
+        };
+
+        if visual.kind == VisualKind::Block && !lines && !matches!(operator, Operator::Indent | Operator::Dedent | Operator::Filter) {
+            let ranges = self.block_ranges();
+            let left = visual.anchor.0.min(self.cx);
+
+            self.exit_visual();
+            self.operate_block(operator, ranges, left, register);
+            return;
+        }
+
+        let Some((range, linewise)) = self.selection_range(lines || visual.kind == VisualKind::Block) else {
+            return;
+        };
+
+        self.exit_visual();
+
+        if let Operator::Indent | Operator::Dedent = operator {
+            let first = self.buffer.byte_to_line(range.start);
+            let last = self.buffer.byte_to_line(range.end - 1);
+
+            for _ in 0..count {
+                self.operate(operator, self.lines_range(first, last), true, register);
+            }
+        } else {
+            self.operate(operator, range, linewise, register);
+        }
+    }
+
+    /// Applies `operator` to each of the `ranges` of a block selection, whose first column is
+    /// `left`. Ranges are changed from the last one, so the offsets of the others stay valid.
+    fn operate_block(&mut self, operator: Operator, ranges: Vec<Range<usize>>, left: usize, register: Option<char>) {
+        let first = self.buffer.byte_to_line(ranges[0].start);
+        let last = first + ranges.len() - 1;
+
+        if let Operator::Delete | Operator::Change | Operator::Yank = operator {
+            let text: Vec<_> = ranges.iter().map(|range| self.buffer.slice(range.clone())).collect();
+            self.store(register, text.join("\n"), RegisterKind::Block, operator == Operator::Yank);
+        }
+
+        for range in ranges.into_iter().rev() {
+            match operator {
+                Operator::Delete | Operator::Change if !range.is_empty() => {
+                    self.buffer_remove(range);
+                },
+                Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+                    self.convert_case(operator, range);
+                },
+                _ => { },
+            }
+        }
+
+        self.cy = first;
+        self.cx = left;
+
+        if operator == Operator::Change {
+            self.cx = left.min(self.get_line_width(0));
+            self.block_insert = Some(BlockInsert { first, last, col: self.cx });
+            self.set_mode(Mode::Insert);
+        }
+    }
+
+    /// Copies the text typed after changing a block to the other lines of the block. Lines too
+    /// short to reach the block are left alone.
+    pub(super) fn finish_block_insert(&mut self) {
+        let Some(block) = self.block_insert.take() else {
+            return;
+        };
+
+        if self.cy != block.first || self.cx <= block.col {
+            return;
+        }
+
+        let start = self.buffer.grapheme_to_byte(block.first, block.col);
+        let text = self.buffer.slice(start..self.cursor_pos()).to_string();
+
+        for n in block.first + 1..=block.last {
+            if self.buffer.line_width(n) >= block.col {
+                self.buffer_insert(self.buffer.grapheme_to_byte(n, block.col), &text);
+            }
+        }
+    }
+
+    /// `J` over a selection, joining all of its lines.
+    pub(super) fn join_selection(&mut self) {
+        let Some(((_, sy), (_, ey))) = self.selection_bounds() else {
+            return;
+        };
+
+        self.exit_visual();
+
+        self.cy = sy;
+        self.join_lines((ey - sy).max(1));
+    }
+
+    /// `r` over a selection, replacing every selected character with `c`.
+    pub(super) fn replace_selection(&mut self, c: char) {
+        let ranges = match self.selection_range(false) {
+            Some((range, _)) => vec![range],
+            None => self.block_ranges(),
+        };
+
+        let Some(((sx, sy), _)) = self.selection_bounds() else {
+            return;
+        };
+        let linewise = matches!(self.mode, Mode::Visual(Visual { kind: VisualKind::Line, .. }));
+
+        self.exit_visual();
+
+        for range in ranges.into_iter().rev() {
+            let text = self.buffer.slice(range.clone());
+            let replaced: String = text
+                .graphemes(true)
+                .map(|g| if g == "\n" { "\n".to_string() } else { c.to_string() })
+                .collect();
+
+            if replaced != text {
+                self.buffer_replace(range, &replaced);
+            }
+        }
+
+        self.cy = sy;
+        self.cx = if linewise { 0 } else { sx };
+    }
 }
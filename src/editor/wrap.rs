@@ -0,0 +1,158 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::util::usize_sub;
+
+use super::{Editor, Mode, movement::Motion};
+
+/// A row of the screen, showing the graphemes `start..end` of the line `line`. With 'wrap', a
+/// long line takes several rows, and the ones after its first start at `column`, after the
+/// indent of 'breakindent' and the marker of 'showbreak'.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ScreenRow {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+    /// The column of the screen where the text of the row starts.
+    pub column: usize,
+    /// Whether the row is the first or the last of its line.
+    pub first: bool,
+    pub last: bool,
+}
+
+impl ScreenRow {
+    /// Whether the cursor at the grapheme `cx` of the line is on the row. The position past the
+    /// end of the line belongs to its last row.
+    pub fn contains(&self, cx: usize) -> bool {
+        self.start <= cx && (cx < self.end || self.last)
+    }
+}
+
+impl Editor {
+    /// Returns the text 'showbreak' puts at the start of the rows continuing a wrapped line.
+    pub fn showbreak(&self) -> &str {
+        &self.options.showbreak
+    }
+
+    /// Returns the rows of the screen the line `n` takes. Without 'wrap', it takes a single row
+    /// starting at the first column on the screen.
+    pub fn line_rows(&self, n: usize) -> Vec<ScreenRow> {
+        let line = self.buffer.line(n);
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
+        let width = graphemes.len();
+
+        if !self.options.wrap || self.columns == 0 {
+            let start = if self.options.wrap { 0 } else { self.left };
+            return vec![ScreenRow { line: n, start, end: width, column: 0, first: true, last: true }];
+        }
+
+        let blank = |g: &str| g == " " || g == "\t";
+
+        // The rows after the first keep at least half the screen for the text
+        let indent = if self.options.breakindent { graphemes.iter().take_while(|g| blank(g)).count() } else { 0 };
+        let continued = (indent + self.showbreak().graphemes(true).count()).min(self.columns / 2);
+
+        let mut rows = vec![];
+        let mut start = 0;
+
+        loop {
+            let column = if rows.is_empty() { 0 } else { continued };
+            let room = usize_sub(self.columns, column).max(1);
+
+            if width - start <= room {
+                rows.push(ScreenRow { line: n, start, end: width, column, first: rows.is_empty(), last: true });
+                return rows;
+            }
+
+            // Break after the last blank that fits, or right at the edge when a word fills the row
+            let end = (start + 1..=start + room)
+                .rev()
+                .find(|&end| blank(graphemes[end - 1]) && !blank(graphemes[end]))
+                .unwrap_or(start + room);
+
+            rows.push(ScreenRow { line: n, start, end, column, first: rows.is_empty(), last: false });
+            start = end;
+        }
+    }
+
+    /// Returns the rows of the screen from the top line, with one more below the last to show the
+    /// part of a line scrolled into view with the mouse.
+    pub fn screen_rows(&self) -> Vec<ScreenRow> {
+        let mut rows = vec![];
+
+        for n in self.top..=self.content_lines() {
+            if rows.len() > self.rows {
+                break;
+            }
+
+            rows.extend(self.line_rows(n));
+        }
+
+        rows.truncate(self.rows + 1);
+        rows
+    }
+
+    /// Returns the position in the text of the cell at `row` and `column` of the screen, as
+    /// `(cx, cy)`, keeping it in the text.
+    pub(super) fn screen_position(&self, row: usize, column: usize) -> (usize, usize) {
+        let rows = self.screen_rows();
+        let Some(row) = rows.get(row).or(rows.last()) else {
+            return (0, 0);
+        };
+
+        let cx = row.start + usize_sub(column, row.column);
+
+        // Only insert mode goes past the last character of a line
+        let end = if row.last && matches!(self.mode, Mode::Insert) { row.end } else { usize_sub(row.end, 1) };
+        (cx.min(end), row.line)
+    }
+
+    /// Returns where `gj`, `gk`, `g0` and `g$` move the cursor to, going through the rows of the
+    /// screen rather than the lines.
+    pub(super) fn screen_motion_target(&self, motion: Motion, n: usize) -> Option<(usize, usize)> {
+        let rows = self.line_rows(self.cy);
+        let index = rows.iter().position(|row| row.contains(self.cx)).unwrap_or(0);
+        let row = rows[index];
+
+        let last_cx = |row: &ScreenRow| usize_sub(row.end, 1);
+
+        match motion {
+            Motion::ScreenLineStart => Some((row.start.min(last_cx(&row)), self.cy)),
+            Motion::ScreenLineEnd => {
+                // Without 'wrap', the row ends at the right edge of the screen
+                let end = if self.options.wrap || self.columns == 0 { last_cx(&row) } else { last_cx(&row).min(row.start + self.columns - 1) };
+                Some((end, self.cy))
+            },
+            Motion::ScreenDown | Motion::ScreenUp => {
+                let down = motion == Motion::ScreenDown;
+                let column = row.column + usize_sub(self.cx, row.start);
+
+                let (mut line, mut index, mut rows) = (self.cy, index, rows);
+                for _ in 0..n {
+                    if down && index + 1 < rows.len() {
+                        index += 1;
+                    } else if !down && index > 0 {
+                        index -= 1;
+                    } else if down && line < self.content_lines() {
+                        line += 1;
+                        rows = self.line_rows(line);
+                        index = 0;
+                    } else if !down && line > 0 {
+                        line -= 1;
+                        rows = self.line_rows(line);
+                        index = rows.len() - 1;
+                    } else {
+                        break;
+                    }
+                }
+
+                if (line, index) == (self.cy, self.line_rows(self.cy).iter().position(|row| row.contains(self.cx)).unwrap_or(0)) {
+                    return None;
+                }
+
+                let row = rows[index];
+                Some(((row.start + usize_sub(column, row.column)).min(last_cx(&row)), line))
+            },
+            _ => None,
+        }
+    }
+}
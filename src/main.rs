@@ -56,15 +56,23 @@ fn main() {
     // ----
     // Setup Echo
     // ----
+    let file = std::env::args().nth(1);
     let mut window = window::Window::new(
         WIDTH as f32, HEIGHT as f32,
         FONT, FONT_H,
+        file.as_deref(),
+        sdl_video_subsystem.clipboard(),
     ).unwrap();
     
     // window.theme.set_hex_cs("#fa0a1e", 123).unwrap();
     window.theme.set_hex_cs("#ffffff", 255).unwrap();
     window.theme.set_hex_bg("#030e8c").unwrap();
     window.theme.set_hex_fg("#fa0a1e").unwrap();
+    window.theme.set_hex_selection("#2b3bb8", 255).unwrap();
+    window.theme.set_hex_search("#8c6d03", 255).unwrap();
+    window.theme.set_hex_gutter("#020a6e", 255).unwrap();
+    window.theme.set_hex_line_nr("#7882c8").unwrap();
+    window.theme.set_hex_current_line_nr("#ffffff").unwrap();
 
     'running: loop {
         start = sdl_timer.performance_counter();
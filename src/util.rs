@@ -7,6 +7,13 @@ pub fn usize_sub(a: usize, b: usize) -> usize {
     }
 }
 
+/// Hashes `data` with the 64-bit FNV-1a function, which is stable across runs and platforms.
+pub fn fnv1a(data: impl IntoIterator<Item = u8>) -> u64 {
+    data.into_iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 // pub mod macros {
 //     macro_rules! usize_sub {
 //         () => {
@@ -1,5 +1,9 @@
+mod mouse;
+mod scroll;
 mod theme;
-use crate::{editor, atlas};
+use std::ffi::CString;
+
+use crate::{editor, atlas, shader::{Program, new_shader}};
 
 
 #[allow(dead_code)]
@@ -11,8 +15,15 @@ pub struct Window {
     fps: f64,
 
     scale: f32,
+    /// How far the text is scrolled, in pixels.
+    scroll: scroll::SmoothScroll,
     atlas: atlas::Atlas,
     editor: editor::Editor,
+
+    /// The OpenGL program responsible for rendering the text in the editor.
+    t_program: Program,
+    /// OpenGL program of the cursor.
+    c_program: Program,
 }
 
 pub enum WindowEvent {
@@ -22,12 +33,45 @@ pub enum WindowEvent {
 }
 
 impl Window {
-    pub fn new(w_width: f32, w_height: f32, font: &str, font_h: u32) -> Result<Self, String> {
+    pub fn new(w_width: f32, w_height: f32, font: &str, font_h: u32, file: Option<&str>, clipboard: sdl2::clipboard::ClipboardUtil) -> Result<Self, String> {
         let w_theme = theme::Theme::default();
-        let editor = editor::Editor::new(w_width as f32, w_height as f32, "")?;
+
+        let t_program = Program::new(
+            &[
+                new_shader(&CString::new(include_str!("../shader/char.v.glsl")).unwrap(), gl::VERTEX_SHADER).unwrap(),
+                new_shader(&CString::new(include_str!("../shader/char.f.glsl")).unwrap(), gl::FRAGMENT_SHADER).unwrap(),
+            ],
+            w_width,
+            w_height,
+        )?;
+
+        let c_program = Program::new(
+            &[
+                new_shader(&CString::new(include_str!("../shader/cursor.v.glsl")).unwrap(), gl::VERTEX_SHADER)?,
+                new_shader(&CString::new(include_str!("../shader/cursor.f.glsl")).unwrap(), gl::FRAGMENT_SHADER)?,
+            ],
+            w_width,
+            w_height,
+        )?;
+
+        let mut editor = editor::Editor::new("");
+        editor.clipboard = Some(clipboard);
+        editor.themes = theme::NAMES.iter().map(|name| name.to_string()).collect();
+        if let Some(path) = editor::prompt::PromptHistory::default_path() {
+            editor.prompt_history = editor::prompt::PromptHistory::load(path);
+        }
+        if let Some(path) = file {
+            let path = std::path::Path::new(path);
+
+            // A file that cannot be read still opens, empty, so the error can be shown
+            if let Err(e) = editor.open(path) {
+                editor.file = Some(editor::file::File::new(path));
+                editor.message = Some(e);
+            }
+        }
         let atlas = atlas::Atlas::new(font, font_h)?;
 
-        Ok(Window {
+        let mut window = Window {
             atlas,
 
             width: w_width,
@@ -37,8 +81,17 @@ impl Window {
             fps: 0.0,
 
             scale: 1.0,
+            scroll: scroll::SmoothScroll::default(),
             editor,
-        })
+
+            t_program,
+            c_program,
+        };
+
+        let (rows, columns) = window.text_size();
+        window.editor.resize(rows, columns);
+
+        Ok(window)
     }
 
     pub fn handle_event(&mut self, event: sdl2::event::Event) -> WindowEvent {
@@ -46,10 +99,34 @@ impl Window {
             sdl2::event::Event::Quit { .. } => {
                 WindowEvent::Quit
             },
+            sdl2::event::Event::MouseButtonDown { mouse_btn, clicks, x, y, .. } => {
+                self.mouse_down(mouse_btn, clicks, x, y);
+                WindowEvent::Nothing
+            },
+            sdl2::event::Event::MouseMotion { mousestate, x, y, .. } if mousestate.left() => {
+                self.mouse_drag(x, y);
+                WindowEvent::Nothing
+            },
+            sdl2::event::Event::MouseButtonUp { mouse_btn, .. } => {
+                self.mouse_up(mouse_btn);
+                WindowEvent::Nothing
+            },
+            sdl2::event::Event::MouseWheel { precise_x, precise_y, direction, .. } => {
+                self.wheel(precise_x, precise_y, direction);
+                WindowEvent::Nothing
+            },
             _ => {
                 self.editor.event(event);
 
-                WindowEvent::Nothing
+                if let Some(theme) = self.editor.colorscheme.take().and_then(|name| theme::Theme::named(&name)) {
+                    self.theme = theme;
+                }
+
+                if self.editor.quit {
+                    WindowEvent::Quit
+                } else {
+                    WindowEvent::Nothing
+                }
             }
         }
     }
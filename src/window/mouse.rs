@@ -0,0 +1,45 @@
+use sdl2::mouse::MouseButton;
+
+use super::Window;
+
+impl Window {
+    /// Returns the row and the column of the cell of text under the pixel at `x` and `y`, counted
+    /// from the top left corner of the window like SDL does.
+    fn cell(&self, x: i32, y: i32) -> (usize, usize) {
+        let row = (y as f32 + self.scroll_offset()) / self.padding_y();
+        let column = (x as f32 - self.text_x()) / self.padding_x();
+
+        (row.max(0.0) as usize, column.max(0.0) as usize)
+    }
+
+    /// Whether the pixel at `y` is on the bar at the bottom of the window.
+    fn on_bar(&self, y: i32) -> bool {
+        y as f32 >= self.height - self.atlas.max_h * 3.0
+    }
+
+    pub(super) fn mouse_down(&mut self, button: MouseButton, clicks: u8, x: i32, y: i32) {
+        if self.on_bar(y) {
+            return;
+        }
+
+        let (row, column) = self.cell(x, y);
+        match button {
+            MouseButton::Left => self.editor.click(row, column, clicks),
+            MouseButton::Middle => self.editor.middle_click(row, column),
+            _ => { },
+        }
+    }
+
+    /// Drags the selection to the pixel at `x` and `y`. Below the last row of text, it scrolls the
+    /// text down.
+    pub(super) fn mouse_drag(&mut self, x: i32, y: i32) {
+        let (row, column) = self.cell(x, y);
+        self.editor.drag(row, column);
+    }
+
+    pub(super) fn mouse_up(&mut self, button: MouseButton) {
+        if button == MouseButton::Left {
+            self.editor.release();
+        }
+    }
+}
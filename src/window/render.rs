@@ -1,38 +1,104 @@
-use std::ffi::CString;
+use std::{ffi::CString, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{editor::{Mode, wrap::ScreenRow}, util::usize_sub};
 
 use super::Window;
 
 // Util methods for render
 impl Window {
     #[inline]
-    fn padding_x(&self) -> f32 {
+    pub(super) fn padding_x(&self) -> f32 {
         self.atlas.max_w * self.scale
     }
 
     #[inline]
-    fn padding_y(&self) -> f32 {
+    pub(super) fn padding_y(&self) -> f32 {
         (self.atlas.max_h * 1.5) * self.scale
     }
 
+    /// Returns how many lines and columns of text fit between the top of the window and the bar,
+    /// right of the gutter.
+    pub(super) fn text_size(&self) -> (usize, usize) {
+        let rows = (self.height - self.atlas.max_h * 3.0) / self.padding_y();
+        let columns = (self.width - self.text_x()) / self.padding_x();
+
+        (rows.max(1.0) as usize, (columns.max(1.0)) as usize)
+    }
+
+    /// Returns the x where the text starts, after a column of margin and the gutter.
+    pub(super) fn text_x(&self) -> f32 {
+        self.atlas.max_w + self.editor.gutter_width() as f32 * self.padding_x()
+    }
+
+    /// Returns the rows drawn on the screen, which take one more when scrolling with the mouse
+    /// leaves part of a row at the top and at the bottom.
+    fn screen_rows(&self) -> Vec<ScreenRow> {
+        let mut rows = self.editor.screen_rows();
+        if self.scroll_offset() <= 0.0 {
+            rows.truncate(self.editor.size().0);
+        }
+
+        rows
+    }
+
+    /// Returns the y of the bottom of the row `row` of text.
+    fn row_y(&self, row: usize) -> f32 {
+        self.height - self.atlas.max_h * 1.5 - row as f32 * self.padding_y() + self.scroll_offset()
+    }
+
+    /// Draws a rectangle of `colors` with its bottom left corner at `x` and `y`, using the
+    /// cursor's program.
+    unsafe fn draw_rect(&self, x: f32, y: f32, w: f32, h: f32, colors: [f32; 4]) {
+        self.c_program.bind();
+
+        let vertices: [f32; 24] = [
+            x,     y,     0.0, 0.0,
+            x + w, y,     1.0, 0.0,
+            x + w, y + h, 1.0, 1.0,
+
+            x,     y,     0.0, 0.0,
+            x + w, y + h, 1.0, 1.0,
+            x,     y + h, 0.0, 1.0,
+        ];
+
+        let color = gl::GetUniformLocation(self.c_program.id, CString::new("cursorColor").unwrap().as_ptr());
+        gl::Uniform4fv(color, 1, colors.as_ptr());
+
+        gl::BindVertexArray(self.c_program.vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.c_program.vbo);
+        gl::BufferSubData(gl::ARRAY_BUFFER, 0, (vertices.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr, vertices.as_ptr() as *const _);
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+        gl::BindVertexArray(0);
+    }
+
     /// ....
     unsafe fn draw_glyphs(&self, content: &str, mut x: f32, mut y: f32, colors: [f32; 4]) {
         let start_x = x;
 
-        self.editor.t_program.bind();
+        self.t_program.bind();
 
-        let color = gl::GetUniformLocation(self.editor.t_program.id, CString::new("textColor").unwrap().as_ptr());
+        let color = gl::GetUniformLocation(self.t_program.id, CString::new("textColor").unwrap().as_ptr());
         gl::Uniform3fv(color, 1, colors.as_ptr());
-        gl::BindVertexArray(self.editor.t_program.vao);
+        gl::BindVertexArray(self.t_program.vao);
 
-        for c in content.chars() {
-            if c == '\n' {
+        for g in content.graphemes(true) {
+            if g == "\n" {
                 x = start_x; 
                 y -= self.padding_y();
 
                 continue;
             }
 
-            let ch = &self.atlas.characters[c as usize];
+            // Each grapheme takes a single cell, just like the cursor counts them. Only its base
+            // character is drawn, and characters missing from the atlas are drawn as `?`.
+            let c = g.chars().next().unwrap_or(' ');
+            let ch = self.atlas.characters
+                .get(c as usize)
+                .unwrap_or(&self.atlas.characters['?' as usize]);
 
             let w = ch.char_w as f32 * self.scale;
             let h = ch.char_h as f32 * self.scale;
@@ -50,7 +116,7 @@ impl Window {
             ];
 
             gl::BindTexture(gl::TEXTURE_2D, ch.tex_id);
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.editor.t_program.vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.t_program.vbo);
             gl::BufferSubData(gl::ARRAY_BUFFER, 0, (vertices.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr, vertices.as_ptr() as *const _);
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::DrawArrays(gl::TRIANGLES, 0, 6);
@@ -69,74 +135,166 @@ impl Window {
 }
 
 impl Window {
-    pub fn next_frame(&self) {
+    pub fn next_frame(&mut self) {
         println!("{} | {} | {}", self.width, self.padding_y(), self.height/ self.padding_y());
 
+        self.animate_scroll();
+
+        // The gutter grows with the number of lines
+        let (rows, columns) = self.text_size();
+        if self.editor.size() != (rows, columns) {
+            self.editor.resize(rows, columns);
+        }
+
+        let rows = self.screen_rows();
+
         unsafe {
-            // self.render_bar();
-            self.render_text();
-            self.render_cursor();
+            self.render_highlights(&rows);
+            self.render_text(&rows);
+            self.render_gutter(&rows);
+            self.render_cursor(&rows);
+            self.render_bar();
         }
     }
 
-    unsafe fn render_text(&self) {
-        self.draw_glyphs(
-            self.editor.content.as_str(),
-            self.atlas.max_w,
-            self.height as f32 - self.atlas.max_h,
-            self.theme.fg()
-        );
+    /// Draws a rectangle of `colors` behind the `columns` of a line on its `row`, which starts at
+    /// `y`. Columns going up to `usize::MAX` go to the right edge of the window on the last row
+    /// of the line.
+    unsafe fn draw_columns(&self, columns: Range<usize>, row: &ScreenRow, y: f32, colors: [f32; 4]) {
+        // Columns on the other rows of the line, or scrolled out of the screen on the left, are
+        // left out
+        let start = columns.start.max(row.start);
+        let end = if row.last { columns.end } else { columns.end.min(row.end) };
+        if end <= start {
+            return;
+        }
+
+        let x = ((row.column + start - row.start) as f32 * self.atlas.max_w) + self.text_x();
+        let w = if end == usize::MAX {
+            self.width - x
+        } else {
+            (end - start) as f32 * self.atlas.max_w * self.scale
+        };
+
+        self.draw_rect(x, y, w, self.padding_y(), colors);
+    }
+
+    /// Draws the background of the matches of the search and of the text selected in visual
+    /// mode, behind the glyphs.
+    unsafe fn render_highlights(&self, rows: &[ScreenRow]) {
+        for (n, row) in rows.iter().enumerate() {
+            let y = self.row_y(n);
+
+            for columns in self.editor.search_highlights(row.line) {
+                self.draw_columns(columns, row, y, self.theme.search());
+            }
+
+            if let Some(columns) = self.editor.selection_columns(row.line) {
+                self.draw_columns(columns, row, y, self.theme.selection());
+            }
+        }
+    }
+
+    /// Draws the rows on the screen, with 'showbreak' before the ones continuing a wrapped line.
+    /// Only the graphemes between the first and the last column on the screen are sent to the GPU.
+    unsafe fn render_text(&self, rows: &[ScreenRow]) {
+        let (_, columns) = self.text_size();
+        let showbreak = self.editor.showbreak();
+
+        for (n, row) in rows.iter().enumerate() {
+            let y = self.row_y(n) + self.atlas.max_h * 0.5;
+
+            if !row.first && !showbreak.is_empty() {
+                let column = usize_sub(row.column, showbreak.graphemes(true).count());
+                self.draw_glyphs(showbreak, self.text_x() + column as f32 * self.padding_x(), y, self.theme.line_nr());
+            }
+
+            let line = self.editor.buffer.line(row.line);
+            let visible: String = line
+                .graphemes(true)
+                .skip(row.start)
+                .take(usize_sub(row.end, row.start).min(columns))
+                .collect();
+
+            self.draw_glyphs(&visible, self.text_x() + row.column as f32 * self.padding_x(), y, self.theme.fg());
+        }
+    }
+
+    /// Draws the gutter on the left of the text, with the numbers of the lines on the screen next
+    /// to their first rows.
+    unsafe fn render_gutter(&self, rows: &[ScreenRow]) {
+        let width = self.editor.gutter_width();
+        if width == 0 {
+            return;
+        }
+
+        // The blank after the numbers is only half in the gutter
+        let w = self.atlas.max_w + (width as f32 - 0.5) * self.padding_x();
+        let bottom = self.atlas.max_h * 3.0;
+        self.draw_rect(0.0, bottom, w, self.height - bottom, self.theme.gutter());
+
+        for (n, row) in rows.iter().enumerate().filter(|(_, row)| row.first) {
+            let colors = if row.line == self.editor.cy { self.theme.current_line_nr() } else { self.theme.line_nr() };
+
+            self.draw_glyphs(
+                &self.editor.line_number(row.line),
+                self.atlas.max_w,
+                self.row_y(n) + self.atlas.max_h * 0.5,
+                colors
+            );
+        }
     }
 
-    unsafe fn render_cursor(&self) {
-        self.editor.c_program.bind();
+    unsafe fn render_cursor(&self, rows: &[ScreenRow]) {
+        let (cx, cy) = (self.editor.cx, self.editor.cy);
+        let Some(row) = rows.iter().position(|row| row.line == cy && row.contains(cx)) else {
+            return;
+        };
 
         let w: f32;
         let h: f32;
         let x: f32;
         let y: f32;
 
+        let column = rows[row].column + cx - rows[row].start;
+
         match self.editor.mode {
             crate::editor::Mode::Insert => {
                 w = (self.atlas.max_w * 0.1) * self.scale;
                 h = self.padding_y();
 
-                x = (self.editor.cx as f32 * self.atlas.max_w) + self.atlas.max_w;
-                y = (self.height as f32 - self.atlas.max_h * 1.5) - (self.editor.cy as f32 * self.atlas.max_h * 1.5);
+                x = (column as f32 * self.atlas.max_w) + self.text_x();
+                y = self.row_y(row);
             },
             _ => {
                 w = self.atlas.max_w * self.scale;
                 h = self.padding_y();
 
-                x = self.editor.cx as f32 * self.atlas.max_w;
-                y = (self.height as f32 - self.atlas.max_h * 1.5) - (self.editor.cy as f32 * self.atlas.max_h * 1.5);
+                x = (column as f32 * self.atlas.max_w) + self.text_x();
+                y = self.row_y(row);
             },
         };
 
-        let vertices: [f32; 24] = [
-            x,     y,     0.0, 0.0,
-            x + w, y,     1.0, 0.0,
-            x + w, y + h, 1.0, 1.0,
-
-            x,     y,     0.0, 0.0,
-            x + w, y + h, 1.0, 1.0,
-            x,     y + h, 0.0, 1.0,
-        ];
-
-        let color = gl::GetUniformLocation(self.editor.c_program.id, CString::new("cursorColor").unwrap().as_ptr());
-        gl::Uniform4fv(color, 1, self.theme.cs().as_ptr());
+        self.draw_rect(x, y, w, h, self.theme.cs());
+    }
 
-        gl::BindVertexArray(self.editor.c_program.vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, self.editor.c_program.vbo);
-        gl::BufferSubData(gl::ARRAY_BUFFER, 0, (vertices.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr, vertices.as_ptr() as *const _);
-        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+    /// Returns the line typed at the bottom of the window, like a search or a command, with the
+    /// column of its cursor.
+    fn prompt_line(&self) -> Option<(String, Option<usize>)> {
+        let prefix = match self.editor.mode {
+            Mode::Search { forward: true, .. } => '/',
+            Mode::Search { forward: false, .. } => '?',
+            Mode::Command => ':',
+            Mode::Confirm => return self.editor.confirm_message().map(|message| (message, None)),
+            _ => return None,
+        };
 
-        gl::BindVertexArray(0);
+        let line = format!("{prefix}{}", self.editor.prompt.text);
+        Some((line, Some(self.editor.prompt.cursor + 1)))
     }
 
     unsafe fn render_bar(&self) {
-        self.editor.c_program.bind();
+        self.c_program.bind();
 
         // Draw bar
         let w = self.width;
@@ -155,11 +313,11 @@ impl Window {
             x,     y + h, 0.0, 1.0,
         ];
 
-        let color = gl::GetUniformLocation(self.editor.c_program.id, CString::new("cursorColor").unwrap().as_ptr());
+        let color = gl::GetUniformLocation(self.c_program.id, CString::new("cursorColor").unwrap().as_ptr());
         gl::Uniform4fv(color, 1, vec![0.0, 0.0, 0.0, 1.0].as_ptr());
 
-        gl::BindVertexArray(self.editor.c_program.vao);
-        gl::BindBuffer(gl::ARRAY_BUFFER, self.editor.c_program.vbo);
+        gl::BindVertexArray(self.c_program.vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.c_program.vbo);
         gl::BufferSubData(gl::ARRAY_BUFFER, 0, (vertices.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr, vertices.as_ptr() as *const _);
         gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         gl::DrawArrays(gl::TRIANGLES, 0, 6);
@@ -168,17 +326,110 @@ impl Window {
 
         self.left_bar();
         self.right_bar();
+        self.render_wildmenu();
+        self.render_message();
+    }
+
+    /// Draws the lines of a message longer than a line in rows above the bar, whose first line
+    /// shows its last one.
+    unsafe fn render_message(&self) {
+        let Some(message) = &self.editor.message else {
+            return;
+        };
+
+        let lines: Vec<&str> = message.lines().collect();
+        let above = lines.len().saturating_sub(1);
+        if above == 0 {
+            return;
+        }
+
+        let bottom = self.atlas.max_h * 3.0;
+        self.draw_rect(0.0, bottom, self.width, above as f32 * self.padding_y(), [0.0, 0.0, 0.0, 1.0]);
+
+        for (n, line) in lines[..above].iter().enumerate() {
+            let y = bottom + (above - n - 1) as f32 * self.padding_y();
+            self.draw_glyphs(line, self.atlas.max_w, y + self.atlas.max_h * 0.5, self.theme.fg());
+        }
+    }
+
+    /// Draws the candidates completing the command being typed in a row above the bar, with the
+    /// one shown over the selection color. The row starts further to the right when the one shown
+    /// does not fit.
+    unsafe fn render_wildmenu(&self) {
+        let Some((candidates, selected)) = self.editor.wildmenu() else {
+            return;
+        };
+
+        let y = self.atlas.max_h * 3.0;
+        self.draw_rect(0.0, y, self.width, self.padding_y(), [0.0, 0.0, 0.0, 1.0]);
+
+        // Each candidate is followed by two blank columns
+        let columns = ((self.width / self.padding_x()) as usize).saturating_sub(2);
+        let widths: Vec<usize> = candidates.iter().map(|candidate| candidate.graphemes(true).count() + 2).collect();
+
+        let mut first = 0;
+        if let Some(selected) = selected {
+            while first < selected && widths[first..=selected].iter().sum::<usize>() > columns {
+                first += 1;
+            }
+        }
+
+        let mut x = self.atlas.max_w;
+        let mut used = 0;
+        for (n, candidate) in candidates.iter().enumerate().skip(first) {
+            if used + widths[n] > columns && n > first {
+                break;
+            }
+
+            if selected == Some(n) {
+                let w = (widths[n] - 2) as f32 * self.padding_x();
+                self.draw_rect(x, y, w, self.padding_y(), self.theme.selection());
+            }
+
+            self.draw_glyphs(candidate, x, y + self.atlas.max_h * 0.5, self.theme.fg());
+
+            x += widths[n] as f32 * self.padding_x();
+            used += widths[n];
+        }
     }
 
     unsafe fn left_bar(&self) {
-        let l1 = format!("{}", self.editor.mode);
-        self.draw_glyphs(
-            l1.as_str(),
-            self.atlas.max_w, self.atlas.max_h,
-            self.theme.fg()
-        );
+        // The line typed at the bottom of the window takes the place of the mode
+        match self.prompt_line() {
+            Some((line, cursor)) => {
+                self.draw_glyphs(&line, self.atlas.max_w, self.atlas.max_h, self.theme.fg());
+
+                if let Some(col) = cursor {
+                    let x = (col as f32 * self.atlas.max_w) + self.atlas.max_w;
+                    self.draw_rect(x, self.atlas.max_h * 0.5, (self.atlas.max_w * 0.1) * self.scale, self.padding_y(), self.theme.cs());
+                }
+            },
+            // A message takes the place of the mode until the next key
+            None if self.editor.message.is_some() => {
+                let message = self.editor.message.as_deref().unwrap_or_default();
+                self.draw_glyphs(
+                    message.lines().last().unwrap_or_default(),
+                    self.atlas.max_w, self.atlas.max_h,
+                    self.theme.fg()
+                );
+            },
+            None => {
+                let l1 = match self.editor.recording() {
+                    Some(register) => format!("{} recording @{register}", self.editor.mode),
+                    None => format!("{}", self.editor.mode),
+                };
+                self.draw_glyphs(
+                    l1.as_str(),
+                    self.atlas.max_w, self.atlas.max_h,
+                    self.theme.fg()
+                );
+            },
+        }
 
-        let l2 = format!("{}", "~/something/foo.rs");
+        let l2 = match &self.editor.file {
+            Some(file) => format!("{}{}", file.path.display(), if self.editor.dirty { " [+]" } else { "" }),
+            None => "[No Name]".to_string(),
+        };
         self.draw_glyphs(
             l2.as_str(),
             self.atlas.max_w, self.atlas.max_h * 2.0,
@@ -186,22 +437,37 @@ impl Window {
         );
     }
 
+    /// Returns how far the screen is through the text, like vim shows it.
+    fn scroll_position(&self) -> String {
+        let lines = self.editor.buffer.len_lines();
+        let visible = self.editor.visible_lines();
+
+        match (visible.start == 0, visible.end >= lines) {
+            (true, true) => "All".to_string(),
+            (true, false) => "Top".to_string(),
+            (false, true) => "Bot".to_string(),
+            (false, false) => format!("{}%", visible.start * 100 / (lines - visible.len())),
+        }
+    }
+
     /// In the end, the screen should render something like:
     /// ```txt
     /// ~ 1,1       0%
     /// ~          fps
     /// ```
     unsafe fn right_bar(&self) {
-        let l1 = format!("{:.1$}", self.fps, 1);
-        self.draw_glyphs(
-            l1.as_str(),
-            self.width - (l1.len() as f32 * self.atlas.max_w) - self.atlas.max_w, self.atlas.max_h,
-            self.theme.fg()
-        );
+        if self.prompt_line().is_none() {
+            let l1 = format!("{:.1$}", self.fps, 1);
+            self.draw_glyphs(
+                l1.as_str(),
+                self.width - (l1.len() as f32 * self.atlas.max_w) - self.atlas.max_w, self.atlas.max_h,
+                self.theme.fg()
+            );
+        }
         
         // - Since cx and cy represent the (x,y) coordinates starting from (0,0), we add 1 to both 
         //   to obtain a more intuitive number.
-        let l2 = format!("{},{}\t\t\t\t\t{}", self.editor.cx + 1, self.editor.cy + 1, "TOP");
+        let l2 = format!("{},{}\t\t\t\t\t{}", self.editor.cx + 1, self.editor.cy + 1, self.scroll_position());
         self.draw_glyphs(
             l2.as_str(),
             self.width - (l2.len() as f32 * self.atlas.max_w) - self.atlas.max_w, self.atlas.max_h * 2.0,
@@ -0,0 +1,108 @@
+use std::time::Instant;
+
+use sdl2::mouse::MouseWheelDirection;
+
+use super::Window;
+
+/// How many lines a notch of the mouse wheel scrolls.
+const WHEEL_LINES: f32 = 3.0;
+
+/// How fast the scroll catches up with where the wheel sent it, as the fraction of the way left
+/// it goes in a second is `1 - e^-SPEED`.
+const SPEED: f32 = 18.0;
+
+/// The vertical scroll of the text, in pixels from the top of its first line, which eases toward
+/// where the mouse wheel sends it.
+pub struct SmoothScroll {
+    /// Where the text is drawn from.
+    pub current: f32,
+    /// Where the text is going to.
+    target: f32,
+    /// When the scroll last moved toward its target.
+    last: Instant,
+    /// The part of a line the wheel scrolled with 'wrap' that did not move the text yet.
+    lines: f32,
+}
+
+impl Default for SmoothScroll {
+    fn default() -> Self {
+        Self {
+            current: 0.0,
+            target: 0.0,
+            last: Instant::now(),
+            lines: 0.0,
+        }
+    }
+}
+
+impl Window {
+    /// Scrolls the text by `x` and `y` notches of the wheel, which can be fractions of one on
+    /// trackpads. The cursor stays where it is, even out of the screen, until the next key.
+    pub(super) fn wheel(&mut self, x: f32, y: f32, direction: MouseWheelDirection) {
+        let (x, y) = match direction {
+            MouseWheelDirection::Flipped => (-x, -y),
+            _ => (x, y),
+        };
+
+        // Wrapped lines take a different number of rows each, so they scroll by whole lines
+        if self.editor.options.wrap {
+            self.scroll.lines -= y * WHEEL_LINES;
+            let lines = self.scroll.lines.trunc();
+            self.scroll.lines -= lines;
+
+            let last = self.editor.buffer.len_lines().saturating_sub(1);
+            self.editor.top = self.editor.top.saturating_add_signed(lines as isize).min(last);
+            self.editor.scrolled = true;
+            return;
+        }
+
+        let line_h = self.padding_y();
+        let max = self.editor.buffer.len_lines().saturating_sub(1) as f32 * line_h;
+
+        // The scroll starts from the line the keys last left at the top
+        if !self.editor.scrolled {
+            self.scroll.target = self.scroll.current;
+        }
+
+        self.scroll.target = (self.scroll.target - y * WHEEL_LINES * line_h).clamp(0.0, max);
+        self.scroll.last = Instant::now();
+
+        // Wrapped lines have nothing past the right edge to scroll to
+        if !self.editor.options.wrap {
+            let columns = (x * WHEEL_LINES).round() as isize;
+            self.editor.left = self.editor.left.saturating_add_signed(columns);
+        }
+
+        self.editor.scrolled = true;
+    }
+
+    /// Moves the scroll toward its target, easing out, and shows the lines it got to.
+    pub(super) fn animate_scroll(&mut self) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.scroll.last).as_secs_f32();
+        self.scroll.last = now;
+
+        let line_h = self.padding_y();
+
+        // Keys, and the wheel with 'wrap', scroll by whole lines right away
+        if !self.editor.scrolled || self.editor.options.wrap {
+            self.scroll.current = self.editor.top as f32 * line_h;
+            self.scroll.target = self.scroll.current;
+            return;
+        }
+
+        let left = self.scroll.target - self.scroll.current;
+        self.scroll.current = if left.abs() < 0.5 {
+            self.scroll.target
+        } else {
+            self.scroll.current + left * (1.0 - (-SPEED * dt).exp())
+        };
+
+        self.editor.top = (self.scroll.current / line_h) as usize;
+    }
+
+    /// Returns how many pixels of the top line on the screen are scrolled above it.
+    pub(super) fn scroll_offset(&self) -> f32 {
+        self.scroll.current - self.editor.top as f32 * self.padding_y()
+    }
+}
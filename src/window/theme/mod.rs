@@ -44,6 +44,15 @@ pub struct Theme {
     bg: Color,
     fg: Color,
     cs: Color,
+    /// The background of the text selected in visual mode.
+    selection: Color,
+    /// The background of the matches of the search.
+    search: Color,
+    /// The background of the gutter showing the line numbers.
+    gutter: Color,
+    /// The line numbers, and the number of the line of the cursor.
+    line_nr: Color,
+    current_line_nr: Color,
 }
 
 impl Default for Theme {
@@ -52,11 +61,47 @@ impl Default for Theme {
             bg: Color::new(0, 0, 0, 255),
             fg: Color::new(255, 255, 255, 255),
             cs: Color::new(255, 255, 255, 255),
+            selection: Color::new(68, 68, 68, 255),
+            search: Color::new(128, 102, 0, 255),
+            gutter: Color::new(17, 17, 17, 255),
+            line_nr: Color::new(110, 110, 110, 255),
+            current_line_nr: Color::new(255, 255, 255, 255),
         }
     }
 }
 
+/// The names of the built-in themes, which `:colorscheme` switches to.
+pub const NAMES: &[&str] = &["default", "echo", "light"];
+
 impl Theme {
+    /// Returns the built-in theme called `name`, if there is one.
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            "echo" => Some(Self {
+                bg: Color::new(3, 14, 140, 255),
+                fg: Color::new(250, 10, 30, 255),
+                cs: Color::new(255, 255, 255, 255),
+                selection: Color::new(43, 59, 184, 255),
+                search: Color::new(140, 109, 3, 255),
+                gutter: Color::new(2, 10, 110, 255),
+                line_nr: Color::new(120, 130, 200, 255),
+                current_line_nr: Color::new(255, 255, 255, 255),
+            }),
+            "light" => Some(Self {
+                bg: Color::new(250, 250, 250, 255),
+                fg: Color::new(30, 30, 30, 255),
+                cs: Color::new(30, 30, 30, 255),
+                selection: Color::new(200, 200, 200, 255),
+                search: Color::new(255, 222, 102, 255),
+                gutter: Color::new(240, 240, 240, 255),
+                line_nr: Color::new(150, 150, 150, 255),
+                current_line_nr: Color::new(30, 30, 30, 255),
+            }),
+            _ => None,
+        }
+    }
+
     /// Sets the background color using a hexadecimal string.
     pub fn set_hex_bg(&mut self, hex: &str) -> Result<(), String> {
         let color = Srgb::<u8>::from_str(hex).map_err(|e| e.to_string())?;
@@ -89,4 +134,69 @@ impl Theme {
     pub fn cs(&self) -> [f32; 4] {
         self.cs.get().map(|c| c as f32 / 255.0)
     }
+
+    /// Sets the selection color using a hexadecimal string.
+    pub fn set_hex_selection(&mut self, hex: &str, alpha: u8) -> Result<(), String> {
+        let color = Srgb::<u8>::from_str(hex).map_err(|e| e.to_string())?;
+        self.selection.set(color.red, color.green, color.blue, alpha);
+        Ok(())
+    }
+
+    /// Returns the selection color as an array of 4 floats in the format \[R, G, B, A].
+    /// Each component is normalized to the range 0~1.
+    pub fn selection(&self) -> [f32; 4] {
+        self.selection.get().map(|c| c as f32 / 255.0)
+    }
+
+    /// Sets the search color using a hexadecimal string.
+    pub fn set_hex_search(&mut self, hex: &str, alpha: u8) -> Result<(), String> {
+        let color = Srgb::<u8>::from_str(hex).map_err(|e| e.to_string())?;
+        self.search.set(color.red, color.green, color.blue, alpha);
+        Ok(())
+    }
+
+    /// Returns the search color as an array of 4 floats in the format \[R, G, B, A].
+    /// Each component is normalized to the range 0~1.
+    pub fn search(&self) -> [f32; 4] {
+        self.search.get().map(|c| c as f32 / 255.0)
+    }
+
+    /// Sets the gutter color using a hexadecimal string.
+    pub fn set_hex_gutter(&mut self, hex: &str, alpha: u8) -> Result<(), String> {
+        let color = Srgb::<u8>::from_str(hex).map_err(|e| e.to_string())?;
+        self.gutter.set(color.red, color.green, color.blue, alpha);
+        Ok(())
+    }
+
+    /// Returns the gutter color as an array of 4 floats in the format \[R, G, B, A].
+    /// Each component is normalized to the range 0~1.
+    pub fn gutter(&self) -> [f32; 4] {
+        self.gutter.get().map(|c| c as f32 / 255.0)
+    }
+
+    /// Sets the line number color using a hexadecimal string.
+    pub fn set_hex_line_nr(&mut self, hex: &str) -> Result<(), String> {
+        let color = Srgb::<u8>::from_str(hex).map_err(|e| e.to_string())?;
+        self.line_nr.set(color.red, color.green, color.blue, 255);
+        Ok(())
+    }
+
+    /// Returns the line number color as an array of 4 floats in the format \[R, G, B, A].
+    /// Each component is normalized to the range 0~1.
+    pub fn line_nr(&self) -> [f32; 4] {
+        self.line_nr.get().map(|c| c as f32 / 255.0)
+    }
+
+    /// Sets the color of the number of the cursor's line using a hexadecimal string.
+    pub fn set_hex_current_line_nr(&mut self, hex: &str) -> Result<(), String> {
+        let color = Srgb::<u8>::from_str(hex).map_err(|e| e.to_string())?;
+        self.current_line_nr.set(color.red, color.green, color.blue, 255);
+        Ok(())
+    }
+
+    /// Returns the color of the number of the cursor's line as an array of 4 floats in the
+    /// format \[R, G, B, A]. Each component is normalized to the range 0~1.
+    pub fn current_line_nr(&self) -> [f32; 4] {
+        self.current_line_nr.get().map(|c| c as f32 / 255.0)
+    }
 }